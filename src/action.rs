@@ -1,7 +1,7 @@
 use core::fmt::{Formatter, Display};
 
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Debug)]
 pub struct  Action {
     pub(crate) company: String,
     pub(crate) collected: bool
@@ -15,14 +15,14 @@ impl Action {
 
 impl Clone for Action {
     fn clone(&self) -> Self {
-        Self { company: self.company.clone(), collected: self.collected.clone() }
+        Self { company: self.company.clone(), collected: self.collected }
     }
 }
 
 impl Display for Action {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         if self.collected {
-            write!(f, "({}:{})", self.company, "Collected")?;
+            write!(f, "({}:Collected)", self.company)?;
         } else {
             write!(f, "({})", self.company)?;
         }
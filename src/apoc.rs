@@ -0,0 +1,119 @@
+use std::collections::{HashMap, HashSet};
+
+use serde_json::{json, Value};
+
+use crate::{Node, Relationships};
+use crate::formats::{build, node_from_properties, optional_properties, sorted_names, PropertyNames};
+
+fn id_of(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// Reads the output of `apoc.export.json.*`, either one JSON object per line or a JSON array.
+/// Nodes without the swag and time price properties and relationships of other types than
+/// `names.path_type` are skipped.
+pub fn apoc_json_to_structures(text: &str, names: &PropertyNames) -> Result<(HashMap<String, Node>, Relationships), String> {
+    let entries: Vec<Value> = if text.trim_start().starts_with('[') {
+        serde_json::from_str(text).map_err(|e| format!("Could not parse the APOC export: {}", e))?
+    } else {
+        text.lines().filter(|l| !l.trim().is_empty()).enumerate()
+            .map(|(i, l)| serde_json::from_str(l).map_err(|e| format!("Line {}: {}", i + 1, e)))
+            .collect::<Result<_, _>>()?
+    };
+
+    let empty = serde_json::Map::new();
+    let mut nodes = vec![];
+    let mut relationships = vec![];
+    for entry in &entries {
+        let properties = entry["properties"].as_object().unwrap_or(&empty);
+        match entry["type"].as_str() {
+            Some("node") => {
+                let id = id_of(&entry["id"]).ok_or("Node without id.")?;
+                if let Some(node) = node_from_properties(&id, properties, names) {
+                    nodes.push((id, node));
+                }
+            }
+            Some("relationship") => {
+                if names.path_type.as_ref().is_some_and(|t| entry["label"].as_str() != Some(t)) {
+                    continue;
+                }
+                let start = id_of(&entry["start"]["id"]).ok_or("Relationship without start id.")?;
+                let end = id_of(&entry["end"]["id"]).ok_or("Relationship without end id.")?;
+                if let Some(cost) = properties.get(&names.path_cost).and_then(|c| c.as_i64()) {
+                    relationships.push((start, end, cost));
+                }
+            }
+            _ => {}
+        }
+    }
+    // Paths between skipped nodes are not paths between companies.
+    let ids: HashSet<&String> = nodes.iter().map(|(id, _)| id).collect();
+    let relationships = relationships.into_iter()
+        .filter(|(from, to, _)| ids.contains(from) && ids.contains(to))
+        .collect();
+    build(nodes, relationships)
+}
+
+/// JSON lines in the shape `apoc.export.json.all` writes, companies labelled `Company` and paths
+/// of type `names.path_type` or `PATH`.
+pub fn structures_to_apoc_json(nodes: &HashMap<String, Node>, relationships: &Relationships, names: &PropertyNames) -> String {
+    let sorted = sorted_names(nodes);
+    let ids: HashMap<&String, usize> = sorted.iter().enumerate().map(|(i, n)| (*n, i)).collect();
+    let mut lines = vec![];
+    for name in &sorted {
+        let node = &nodes[*name];
+        let mut properties = optional_properties(node);
+        properties.insert(names.name.clone(), json!(name));
+        properties.insert(names.value.clone(), json!(node.value));
+        properties.insert(names.cost.clone(), json!(node.cost));
+        lines.push(json!({"type": "node", "id": ids[name].to_string(), "labels": ["Company"], "properties": properties}));
+    }
+    let mut id = 0;
+    for name in &sorted {
+        for path in relationships.get(*name).iter().flat_map(|rel| &rel.paths) {
+            lines.push(json!({
+                "type": "relationship", "id": id.to_string(),
+                "label": names.path_type.as_deref().unwrap_or("PATH"),
+                "properties": {names.path_cost.clone(): path.cost},
+                "start": {"id": ids[name].to_string(), "labels": ["Company"]},
+                "end": {"id": ids[&path.to].to_string(), "labels": ["Company"]},
+            }));
+            id += 1;
+        }
+    }
+    lines.iter().map(|l| l.to_string() + "\n").collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_json_lines_and_skips_other_nodes() {
+        let export = r#"
+{"type":"node","id":"0","labels":["Company"],"properties":{"name":"Neo4j","swag":350,"timePrice":147}}
+{"type":"node","id":"1","labels":["Company"],"properties":{"name":"Dovu","swag":129,"timePrice":27,"latest":100}}
+{"type":"node","id":"2","labels":["Route"],"properties":{"name":"yesterday"}}
+{"type":"relationship","id":"0","label":"PATH","properties":{"timePrice":24},"start":{"id":"0"},"end":{"id":"1"}}
+{"type":"relationship","id":"1","label":"VISITED","properties":{"step":1},"start":{"id":"2"},"end":{"id":"0"}}
+"#;
+        let (nodes, relationships) = apoc_json_to_structures(export, &PropertyNames::default()).unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes["Dovu"].window.unwrap().latest, 100);
+        assert_eq!(relationships["Neo4j"].paths[0].to, "Dovu");
+        assert!(relationships["Dovu"].paths.is_empty());
+    }
+
+    #[test]
+    fn reads_json_arrays_with_numeric_ids() {
+        let export = r#"[{"type":"node","id":7,"properties":{"name":"A","swag":1,"timePrice":1}},
+                         {"type":"node","id":8,"properties":{"name":"B","swag":2,"timePrice":2}},
+                         {"type":"relationship","id":0,"label":"PATH","properties":{"timePrice":3},"start":{"id":8},"end":{"id":7}}]"#;
+        let (_, relationships) = apoc_json_to_structures(export, &PropertyNames::default()).unwrap();
+        assert_eq!(relationships["B"].paths[0].cost, 3);
+    }
+}
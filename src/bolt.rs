@@ -0,0 +1,367 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+use serde_json::{Map, Value};
+
+use crate::{Node, Relationships};
+use crate::formats::{build, node_from_properties, PropertyNames};
+
+const HELLO: u8 = 0x01;
+const GOODBYE: u8 = 0x02;
+const RUN: u8 = 0x10;
+const PULL: u8 = 0x3F;
+const SUCCESS: u8 = 0x70;
+const RECORD: u8 = 0x71;
+const IGNORED: u8 = 0x7E;
+const FAILURE: u8 = 0x7F;
+
+// Bolt 5.0 and 4.4 down to 4.1 (minor 4 with a range of 3), they agree on everything used here.
+const VERSIONS: [[u8; 4]; 4] = [[0, 0, 0, 5], [0, 3, 4, 4], [0; 4], [0; 4]];
+
+pub const DEFAULT_NODES_QUERY: &str = "MATCH (c:Company) RETURN c.name AS name, c.swag AS swag, c.timePrice AS timePrice";
+pub const DEFAULT_PATHS_QUERY: &str = "MATCH (a:Company)-[p:PATH]->(b:Company) RETURN a.name AS from, b.name AS to, p.timePrice AS timePrice";
+
+/// Writes a value in PackStream, the serialisation of Bolt.
+fn pack(value: &Value, out: &mut Vec<u8>) {
+    fn size(out: &mut Vec<u8>, len: usize, tiny: u8, markers: [u8; 3]) {
+        if len < 16 {
+            out.push(tiny + len as u8);
+        } else if len <= u8::MAX as usize {
+            out.extend([markers[0], len as u8]);
+        } else if len <= u16::MAX as usize {
+            out.push(markers[1]);
+            out.extend((len as u16).to_be_bytes());
+        } else {
+            out.push(markers[2]);
+            out.extend((len as u32).to_be_bytes());
+        }
+    }
+    match value {
+        Value::Null => out.push(0xC0),
+        Value::Bool(b) => out.push(if *b { 0xC3 } else { 0xC2 }),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) if (-16..128).contains(&i) => out.push(i as i8 as u8),
+            Some(i) if i8::try_from(i).is_ok() => out.extend([0xC8, i as i8 as u8]),
+            Some(i) if i16::try_from(i).is_ok() => { out.push(0xC9); out.extend((i as i16).to_be_bytes()); }
+            Some(i) if i32::try_from(i).is_ok() => { out.push(0xCA); out.extend((i as i32).to_be_bytes()); }
+            Some(i) => { out.push(0xCB); out.extend(i.to_be_bytes()); }
+            None => { out.push(0xC1); out.extend(n.as_f64().unwrap_or(0.0).to_be_bytes()); }
+        },
+        Value::String(s) => {
+            size(out, s.len(), 0x80, [0xD0, 0xD1, 0xD2]);
+            out.extend(s.as_bytes());
+        }
+        Value::Array(items) => {
+            size(out, items.len(), 0x90, [0xD4, 0xD5, 0xD6]);
+            items.iter().for_each(|i| pack(i, out));
+        }
+        Value::Object(map) => {
+            size(out, map.len(), 0xA0, [0xD8, 0xD9, 0xDA]);
+            for (k, v) in map {
+                pack(&Value::String(k.clone()), out);
+                pack(v, out);
+            }
+        }
+    }
+}
+
+/// Reads PackStream. Structures (nodes, relationships, dates...) become the list of their fields.
+struct Unpacker<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Unpacker<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let slice = self.bytes.get(self.pos..self.pos + n).ok_or("Truncated Bolt message.")?;
+        self.pos += n;
+        Ok(slice)
+    }
+
+    fn length(&mut self, width: usize) -> Result<usize, String> {
+        let bytes = self.take(width)?;
+        Ok(bytes.iter().fold(0usize, |acc, b| (acc << 8) | *b as usize))
+    }
+
+    fn string(&mut self, len: usize) -> Result<Value, String> {
+        let bytes = self.take(len)?;
+        Ok(Value::String(String::from_utf8_lossy(bytes).to_string()))
+    }
+
+    fn list(&mut self, len: usize) -> Result<Value, String> {
+        (0..len).map(|_| self.value()).collect::<Result<Vec<_>, _>>().map(Value::Array)
+    }
+
+    fn map(&mut self, len: usize) -> Result<Value, String> {
+        let mut map = Map::new();
+        for _ in 0..len {
+            let key = match self.value()? {
+                Value::String(key) => key,
+                other => other.to_string(),
+            };
+            map.insert(key, self.value()?);
+        }
+        Ok(Value::Object(map))
+    }
+
+    /// A structure's signature and fields.
+    fn structure(&mut self) -> Result<(u8, Vec<Value>), String> {
+        let marker = self.take(1)?[0];
+        if marker & 0xF0 != 0xB0 {
+            return Err(format!("Expected a Bolt structure, got marker {:#x}.", marker));
+        }
+        let signature = self.take(1)?[0];
+        let fields = (0..(marker & 0x0F)).map(|_| self.value()).collect::<Result<_, _>>()?;
+        Ok((signature, fields))
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        let marker = self.take(1)?[0];
+        let int = |bytes: &[u8]| bytes.iter().fold(if bytes[0] & 0x80 != 0 { -1i64 } else { 0 }, |acc, b| (acc << 8) | *b as i64);
+        match marker {
+            0x00..=0x7F => Ok(Value::from(marker as i64)),
+            0xF0..=0xFF => Ok(Value::from(marker as i8 as i64)),
+            0x80..=0x8F => self.string((marker & 0x0F) as usize),
+            0x90..=0x9F => self.list((marker & 0x0F) as usize),
+            0xA0..=0xAF => self.map((marker & 0x0F) as usize),
+            0xB0..=0xBF => {
+                self.pos -= 1;
+                Ok(Value::Array(self.structure()?.1))
+            }
+            0xC0 => Ok(Value::Null),
+            0xC1 => Ok(Value::from(f64::from_be_bytes(self.take(8)?.try_into().unwrap()))),
+            0xC2 => Ok(Value::Bool(false)),
+            0xC3 => Ok(Value::Bool(true)),
+            0xC8 => Ok(Value::from(int(self.take(1)?))),
+            0xC9 => Ok(Value::from(int(self.take(2)?))),
+            0xCA => Ok(Value::from(int(self.take(4)?))),
+            0xCB => Ok(Value::from(int(self.take(8)?))),
+            0xCC..=0xCE => {
+                // Bytes, not used by the queries here.
+                let len = self.length(1 << (marker - 0xCC))?;
+                self.take(len)?;
+                Ok(Value::Null)
+            }
+            0xD0..=0xD2 => { let len = self.length(1 << (marker - 0xD0))?; self.string(len) }
+            0xD4..=0xD6 => { let len = self.length(1 << (marker - 0xD4))?; self.list(len) }
+            0xD8..=0xDA => { let len = self.length(1 << (marker - 0xD8))?; self.map(len) }
+            other => Err(format!("Unknown PackStream marker {:#x}.", other)),
+        }
+    }
+}
+
+fn message(signature: u8, fields: &[Value]) -> Vec<u8> {
+    let mut out = vec![0xB0 + fields.len() as u8, signature];
+    fields.iter().for_each(|f| pack(f, &mut out));
+    out
+}
+
+/// Splits a message into chunks, ended by an empty chunk.
+fn write_message(stream: &mut impl Write, message: &[u8]) -> std::io::Result<()> {
+    for chunk in message.chunks(u16::MAX as usize) {
+        stream.write_all(&(chunk.len() as u16).to_be_bytes())?;
+        stream.write_all(chunk)?;
+    }
+    stream.write_all(&[0, 0])?;
+    stream.flush()
+}
+
+fn read_message(stream: &mut impl Read) -> std::io::Result<Vec<u8>> {
+    let mut message = vec![];
+    loop {
+        let mut size = [0u8; 2];
+        stream.read_exact(&mut size)?;
+        let size = u16::from_be_bytes(size) as usize;
+        if size == 0 {
+            if message.is_empty() {
+                continue; // A no-op chunk keeping the connection alive.
+            }
+            return Ok(message);
+        }
+        let start = message.len();
+        message.resize(start + size, 0);
+        stream.read_exact(&mut message[start..])?;
+    }
+}
+
+/// A connection to a Neo4j server speaking Bolt.
+pub struct BoltClient {
+    stream: TcpStream,
+}
+
+impl BoltClient {
+    /// Connects to `bolt://host:port` (or just `host:port`) and logs in with basic auth.
+    pub fn connect(address: &str, user: &str, password: &str) -> Result<BoltClient, String> {
+        let address = address.strip_prefix("bolt://").or(address.strip_prefix("neo4j://")).unwrap_or(address);
+        let address = if address.contains(':') { address.to_string() } else { format!("{}:7687", address) };
+        let mut stream = TcpStream::connect(&address).map_err(|e| format!("Could not connect to {}: {}", address, e))?;
+
+        let mut handshake = vec![0x60, 0x60, 0xB0, 0x17];
+        VERSIONS.iter().for_each(|v| handshake.extend(v));
+        stream.write_all(&handshake).map_err(|e| e.to_string())?;
+        let mut version = [0u8; 4];
+        stream.read_exact(&mut version).map_err(|e| format!("Bolt handshake failed: {}", e))?;
+        if version == [0; 4] {
+            return Err("The server speaks none of the Bolt versions 4.1 to 5.0.".to_string());
+        }
+
+        let mut client = BoltClient { stream };
+        let mut hello = Map::new();
+        hello.insert("user_agent".to_string(), Value::from("arkadNeo4J/0.1"));
+        hello.insert("scheme".to_string(), Value::from("basic"));
+        hello.insert("principal".to_string(), Value::from(user));
+        hello.insert("credentials".to_string(), Value::from(password));
+        client.send(HELLO, &[Value::Object(hello)])?;
+        client.expect_success()?;
+        Ok(client)
+    }
+
+    fn send(&mut self, signature: u8, fields: &[Value]) -> Result<(), String> {
+        write_message(&mut self.stream, &message(signature, fields)).map_err(|e| format!("Could not send to Neo4j: {}", e))
+    }
+
+    fn receive(&mut self) -> Result<(u8, Vec<Value>), String> {
+        let bytes = read_message(&mut self.stream).map_err(|e| format!("Could not read from Neo4j: {}", e))?;
+        Unpacker { bytes: &bytes, pos: 0 }.structure()
+    }
+
+    fn expect_success(&mut self) -> Result<Value, String> {
+        match self.receive()? {
+            (SUCCESS, mut fields) => Ok(fields.pop().unwrap_or(Value::Null)),
+            (FAILURE, fields) => Err(format!("Neo4j failed: {}", fields.first().and_then(|f| f["message"].as_str()).unwrap_or("unknown error"))),
+            (IGNORED, _) => Err("Neo4j ignored the request.".to_string()),
+            (other, _) => Err(format!("Unexpected Bolt message {:#x}.", other)),
+        }
+    }
+
+    /// Runs the query and returns every row keyed by column name.
+    pub fn query(&mut self, cypher: &str) -> Result<Vec<Map<String, Value>>, String> {
+        self.send(RUN, &[Value::from(cypher), Value::Object(Map::new()), Value::Object(Map::new())])?;
+        let summary = self.expect_success()?;
+        let columns: Vec<String> = summary["fields"].as_array().map(|f| f.iter()
+            .map(|c| c.as_str().unwrap_or_default().to_string()).collect()).unwrap_or_default();
+
+        let mut pull = Map::new();
+        pull.insert("n".to_string(), Value::from(-1));
+        self.send(PULL, &[Value::Object(pull)])?;
+        let mut rows = vec![];
+        loop {
+            match self.receive()? {
+                (RECORD, mut fields) => {
+                    let values = match fields.pop() {
+                        Some(Value::Array(values)) => values,
+                        _ => return Err("Malformed Bolt record.".to_string()),
+                    };
+                    rows.push(columns.iter().cloned().zip(values).collect());
+                }
+                (SUCCESS, _) => return Ok(rows),
+                (FAILURE, fields) => return Err(format!("Neo4j failed: {}", fields.first().and_then(|f| f["message"].as_str()).unwrap_or("unknown error"))),
+                (other, _) => return Err(format!("Unexpected Bolt message {:#x}.", other)),
+            }
+        }
+    }
+}
+
+impl Drop for BoltClient {
+    fn drop(&mut self) {
+        let _ = self.send(GOODBYE, &[]);
+    }
+}
+
+/// Reads the companies and paths with the two queries. The nodes query returns the name, swag
+/// and time price columns of `names`, the paths query `from`, `to` and the path cost column.
+pub fn load(client: &mut BoltClient, nodes_query: &str, paths_query: &str,
+            names: &PropertyNames) -> Result<(HashMap<String, Node>, Relationships), String> {
+    let mut nodes = vec![];
+    for row in client.query(nodes_query)? {
+        let name = row.get(&names.name).and_then(|n| n.as_str())
+            .ok_or(format!("Company row without {} column.", names.name))?.to_string();
+        let node = node_from_properties(&name, &row, names)
+            .ok_or(format!("Company {} lacks {} or {}.", name, names.value, names.cost))?;
+        nodes.push((name, node));
+    }
+    let mut edges = vec![];
+    for row in client.query(paths_query)? {
+        let column = |c: &str| row.get(c).and_then(|v| v.as_str()).map(|v| v.to_string())
+            .ok_or(format!("Path row without {} column.", c));
+        let cost = row.get(&names.path_cost).and_then(|c| c.as_i64())
+            .ok_or(format!("Path row without {} column.", names.path_cost))?;
+        edges.push((column("from")?, column("to")?, cost));
+    }
+    build(nodes, edges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::net::TcpListener;
+
+    fn success(stream: &mut TcpStream, metadata: Value) {
+        write_message(stream, &message(SUCCESS, &[metadata])).unwrap();
+    }
+
+    /// Answers like Neo4j would for the default queries on a three company graph.
+    fn stand_in_server(listener: TcpListener) {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut handshake = [0u8; 20];
+        stream.read_exact(&mut handshake).unwrap();
+        assert_eq!(handshake[..4], [0x60, 0x60, 0xB0, 0x17]);
+        // 4.3 is in the range offered from 4.4.
+        let (range, minor) = (handshake[9], handshake[10]);
+        assert!(handshake[11] == 4 && minor >= 3 && minor - range <= 3);
+        stream.write_all(&[0, 0, 4, 4]).unwrap();
+
+        let hello = read_message(&mut stream).unwrap();
+        let (signature, fields) = Unpacker { bytes: &hello, pos: 0 }.structure().unwrap();
+        assert_eq!(signature, HELLO);
+        assert_eq!(fields[0]["credentials"], "secret");
+        success(&mut stream, json!({"server": "Neo4j/5.0.0"}));
+
+        for _ in 0..2 {
+            let run = read_message(&mut stream).unwrap();
+            let (_, fields) = Unpacker { bytes: &run, pos: 0 }.structure().unwrap();
+            let (columns, rows) = if fields[0] == DEFAULT_NODES_QUERY {
+                (json!(["name", "swag", "timePrice"]), vec![json!(["Neo4j", 0, 0]), json!(["Arkad", 300, 40]), json!(["Ünicode", -5, 70000])])
+            } else {
+                (json!(["from", "to", "timePrice"]), vec![json!(["Neo4j", "Arkad", 12]), json!(["Arkad", "Ünicode", 25])])
+            };
+            success(&mut stream, json!({"fields": columns}));
+            read_message(&mut stream).unwrap();
+            for row in rows {
+                write_message(&mut stream, &message(RECORD, &[row])).unwrap();
+            }
+            success(&mut stream, json!({}));
+        }
+    }
+
+    #[test]
+    fn packs_and_unpacks_values() {
+        let value = json!({"tiny": -16, "byte": -17, "short": 1000, "int": 70000, "long": 5_000_000_000i64,
+                           "float": 1.5, "list": [null, true, false], "text": "x".repeat(300)});
+        let mut bytes = vec![];
+        pack(&value, &mut bytes);
+        assert_eq!(Unpacker { bytes: &bytes, pos: 0 }.value().unwrap(), value);
+    }
+
+    #[test]
+    fn loads_graph_from_stand_in_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("bolt://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || stand_in_server(listener));
+
+        let mut client = BoltClient::connect(&address, "neo4j", "secret").unwrap();
+        let result = load(&mut client, DEFAULT_NODES_QUERY, DEFAULT_PATHS_QUERY, &PropertyNames::default());
+        drop(client);
+        server.join().unwrap();
+
+        let (nodes, relationships) = result.unwrap();
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(nodes["Arkad"].value, 300);
+        assert_eq!(nodes["Ünicode"].cost, 70000);
+        assert_eq!(relationships["Arkad"].paths[0].to, "Ünicode");
+        assert_eq!(relationships["Arkad"].paths[0].cost, 25);
+        assert!(relationships["Ünicode"].paths.is_empty());
+    }
+}
@@ -0,0 +1,43 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Command line of the form `arkadNeo4J [command] [--option value | --flag]...`.
+pub struct Args {
+    pub(crate) command: String,
+    options: HashMap<String, String>,
+}
+
+impl Args {
+    pub fn from_env() -> Args {
+        Args::parse(std::env::args().skip(1))
+    }
+
+    pub fn parse(args: impl Iterator<Item = String>) -> Args {
+        let mut command = "solve".to_string();
+        let mut options = HashMap::new();
+        let mut args = args.peekable();
+        while let Some(arg) = args.next() {
+            if let Some(key) = arg.strip_prefix("--") {
+                let value = match args.peek() {
+                    Some(next) if !next.starts_with("--") => args.next().unwrap(),
+                    _ => "true".to_string(),
+                };
+                options.insert(key.to_string(), value);
+            } else {
+                command = arg;
+            }
+        }
+        Args{command, options}
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.options.get(key).map(|v| v.as_str())
+    }
+
+    pub fn get_or<T: FromStr>(&self, key: &str, default: T) -> T {
+        match self.get(key) {
+            Some(value) => value.parse().unwrap_or_else(|_| panic!("Invalid value {} for --{}", value, key)),
+            None => default,
+        }
+    }
+}
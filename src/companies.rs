@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// A set of companies as a bitset. The index of the companies is shared between clones, so
+/// copying a set costs a few words per 64 companies.
+#[derive(Clone, Default)]
+pub struct CompanySet {
+    ids: Rc<HashMap<String, usize>>,
+    bits: Vec<u64>,
+}
+
+impl CompanySet {
+    /// Gives the companies an index up front, so later inserts do not copy the index.
+    pub fn index<'a>(&mut self, names: impl IntoIterator<Item = &'a String>) {
+        let mut missing: Vec<&String> = names.into_iter().filter(|n| !self.ids.contains_key(*n)).collect();
+        if missing.is_empty() {
+            return;
+        }
+        missing.sort();
+        let ids = Rc::make_mut(&mut self.ids);
+        for name in missing {
+            ids.insert(name.clone(), ids.len());
+        }
+    }
+
+    fn id(&mut self, name: &str) -> usize {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let ids = Rc::make_mut(&mut self.ids);
+        ids.insert(name.to_string(), ids.len());
+        ids.len() - 1
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.ids.get(name).is_some_and(|id| self.bits.get(id / 64).is_some_and(|word| word & (1 << (id % 64)) != 0))
+    }
+
+    /// Adds the company, returns whether it was not in the set yet.
+    pub fn insert(&mut self, name: &str) -> bool {
+        let id = self.id(name);
+        if self.bits.len() <= id / 64 {
+            self.bits.resize(id / 64 + 1, 0);
+        }
+        let added = self.bits[id / 64] & (1 << (id % 64)) == 0;
+        self.bits[id / 64] |= 1 << (id % 64);
+        added
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        if let Some(id) = self.ids.get(name) {
+            if let Some(word) = self.bits.get_mut(id / 64) {
+                *word &= !(1 << (id % 64));
+            }
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::any::Any;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::time::Instant;
+
+use crate::{algorithm_from_spec, Maximizer, Node, Relationships};
+use crate::config::RunConfig;
+use crate::csv::quote;
+use crate::formats::{load, Format, PropertyNames};
+use crate::subnets::Subnets;
+
+pub struct Instance {
+    pub name: String,
+    pub nodes: HashMap<String, Node>,
+    pub relationships: Relationships,
+}
+
+/// Every graph file in the directory, sorted by name. CSV edge files are read along with their
+/// nodes file and files which do not parse are skipped with a warning.
+pub fn load_instances(directory: &str, names: &PropertyNames) -> Result<Vec<Instance>, String> {
+    let mut paths: Vec<_> = fs::read_dir(directory).map_err(|e| format!("Could not read {}: {}", directory, e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && !path.to_string_lossy().ends_with("_edges.csv"))
+        .collect();
+    paths.sort();
+    let mut instances = vec![];
+    for path in paths {
+        let path = path.to_string_lossy().to_string();
+        match load(&path, None, &Format::from_path(&path), names) {
+            Ok((nodes, relationships)) => {
+                let name = path.rsplit('/').next().unwrap_or(&path).to_string();
+                instances.push(Instance { name, nodes, relationships });
+            }
+            Err(e) => eprintln!("Warning: skipping {}: {}", path, e),
+        }
+    }
+    Ok(instances)
+}
+
+/// The results of one algorithm on one instance over all repetitions.
+pub struct Row {
+    pub instance: String,
+    pub algorithm: String,
+    pub runs: usize,
+    pub failures: usize,
+    pub score: Option<i64>,
+    // Percent below the best known score of the instance.
+    pub gap: Option<f64>,
+    pub runtime_ms: f64,
+    pub expanded: f64,
+    // Why the first failed run failed.
+    pub error: Option<String>,
+}
+
+/// The message a panic was raised with.
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload.downcast_ref::<&str>().map_or("unknown panic".to_string(), |m| m.to_string()),
+    }
+}
+
+/// Solves the instance from scratch with the start, budget, rules and objective of `config`,
+/// the score, runtime in ms and nodes expanded.
+pub fn run(instance: &Instance, spec: &str, config: &RunConfig) -> Result<(i64, f64, u64), String> {
+    let state = config.state();
+    if !instance.nodes.contains_key(&state.current_company) {
+        return Err(format!("{} has no start company {}", instance.name, state.current_company));
+    }
+    let algorithm = algorithm_from_spec(spec, state.current_company.clone())?;
+    // Some graphs make the solvers panic (self loops, broken data), that counts as a failed run
+    // with the panic's message.
+    catch_unwind(AssertUnwindSafe(|| {
+        let subnets = Subnets::new(&instance.relationships, &instance.nodes);
+        let value_model = config.value_model(&subnets)?;
+        let mut maximizer = Maximizer::new(state, instance.nodes.clone(), instance.relationships.clone(),
+                                           algorithm, subnets, value_model);
+        maximizer.verbose = false;
+        let start = Instant::now();
+        maximizer.collect();
+        let runtime = start.elapsed().as_secs_f64() * 1000.0;
+        Ok((maximizer.state.score, runtime, maximizer.algorithm.nodes_expanded()))
+    })).unwrap_or_else(|payload| Err(format!("{} failed on {}: {}", spec, instance.name, panic_message(payload))))
+}
+
+/// Runs every algorithm spec `repetitions` times on every instance as `config` sets the run up.
+/// The gap is measured against the best score of any algorithm or `best_known`, whichever is higher.
+pub fn compare(instances: &[Instance], specs: &[String], repetitions: usize,
+               best_known: &HashMap<String, i64>, config: &RunConfig) -> Vec<Row> {
+    let mut rows = vec![];
+    for instance in instances {
+        let first = rows.len();
+        for spec in specs {
+            let mut scores = vec![];
+            let mut runtime = 0.0;
+            let mut expanded = 0;
+            let mut error = None;
+            for _ in 0..repetitions {
+                match run(instance, spec, config) {
+                    Ok((score, ms, nodes)) => {
+                        scores.push(score);
+                        runtime += ms;
+                        expanded += nodes;
+                    }
+                    Err(e) => {
+                        eprintln!("Warning: {}", e);
+                        error.get_or_insert(e);
+                    }
+                }
+            }
+            let successes = scores.len().max(1) as f64;
+            rows.push(Row {
+                instance: instance.name.clone(),
+                algorithm: spec.clone(),
+                runs: repetitions,
+                failures: repetitions - scores.len(),
+                score: scores.iter().max().copied(),
+                gap: None,
+                runtime_ms: runtime / successes,
+                expanded: expanded as f64 / successes,
+                error,
+            });
+        }
+        let best = rows[first..].iter().filter_map(|r| r.score)
+            .chain(best_known.get(&instance.name).copied()).max();
+        for row in &mut rows[first..] {
+            row.gap = match (row.score, best) {
+                (Some(_), Some(0)) => Some(0.0),
+                (Some(score), Some(best)) => Some((best - score) as f64 / best.abs() as f64 * 100.0),
+                _ => None,
+            };
+        }
+    }
+    rows
+}
+
+fn cells(row: &Row) -> [String; 9] {
+    let optional = |v: Option<String>| v.unwrap_or_else(|| "-".to_string());
+    [row.instance.clone(), row.algorithm.clone(), row.runs.to_string(), row.failures.to_string(),
+     optional(row.score.map(|s| s.to_string())), optional(row.gap.map(|g| format!("{:.2}", g))),
+     format!("{:.1}", row.runtime_ms), format!("{:.0}", row.expanded), optional(row.error.clone())]
+}
+
+const HEADER: [&str; 9] = ["instance", "algorithm", "runs", "failures", "score", "gap %", "runtime ms", "nodes expanded",
+                           "error"];
+
+pub fn to_csv(rows: &[Row]) -> String {
+    let mut csv = HEADER.join(",") + "\n";
+    for row in rows {
+        csv += &(cells(row).map(|cell| quote(&cell)).join(",") + "\n");
+    }
+    csv
+}
+
+pub fn to_markdown(rows: &[Row]) -> String {
+    let mut table = String::new();
+    writeln!(table, "| {} |", HEADER.join(" | ")).unwrap();
+    writeln!(table, "|{}", "---|".repeat(HEADER.len())).unwrap();
+    for row in rows {
+        writeln!(table, "| {} |", cells(row).map(|cell| cell.replace('|', "\\|").replace('\n', " ")).join(" | ")).unwrap();
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::{generate, GeneratorConfig};
+    use crate::route;
+    use crate::rules::Rules;
+    use crate::value::Additive;
+
+    #[test]
+    fn measures_gap_to_the_best_algorithm() {
+        let (nodes, relationships) = generate(&GeneratorConfig { nodes: 15, ..GeneratorConfig::default() });
+        let instances = vec![Instance { name: "small".to_string(), nodes, relationships }];
+        let specs = vec!["simple".to_string(), "depth:20".to_string(), "nonsense".to_string()];
+        let rows = compare(&instances, &specs, 2, &HashMap::new(), &RunConfig::default());
+
+        assert_eq!(rows.len(), 3);
+        assert!(rows[..2].iter().all(|r| r.failures == 0 && r.expanded > 0.0));
+        assert!(rows[..2].iter().any(|r| r.gap == Some(0.0)));
+        assert_eq!((rows[2].failures, rows[2].score, rows[2].gap), (2, None, None));
+        assert!(rows[2].error.as_ref().unwrap().contains("nonsense"));
+        assert!(rows[..2].iter().all(|r| r.error.is_none()));
+        assert!(to_csv(&rows).lines().nth(3).unwrap().ends_with(&format!(",{}", quote(rows[2].error.as_ref().unwrap()))));
+
+        let known = HashMap::from([("small".to_string(), rows[0].score.unwrap().max(rows[1].score.unwrap()) * 2)]);
+        let rows = compare(&instances, &specs[..1], 1, &known, &RunConfig::default());
+        assert!(rows[0].gap.unwrap() >= 50.0);
+        assert!(to_markdown(&rows).lines().nth(2).unwrap().starts_with("| small | simple | 1 | 0 |"));
+    }
+
+    #[test]
+    fn failed_runs_keep_the_panic_message() {
+        let payload = catch_unwind(|| panic!("no path from {} to {}", "A", "B")).err().unwrap();
+        assert_eq!(panic_message(payload), "no path from A to B");
+        let payload = catch_unwind(|| panic!("dead end")).err().unwrap();
+        assert_eq!(panic_message(payload), "dead end");
+    }
+
+    #[test]
+    fn runs_as_configured() {
+        let (nodes, relationships) = generate(&GeneratorConfig { nodes: 15, start: "Arkad".to_string(), ..GeneratorConfig::default() });
+        let instance = Instance { name: "arkad".to_string(), nodes, relationships };
+        let config = RunConfig { start: "Arkad".to_string(), budget: 300, value: "diminishing".to_string(), ..RunConfig::default() };
+        let (score, _, _) = run(&instance, "simple", &config).unwrap();
+
+        let subnets = Subnets::new(&instance.relationships, &instance.nodes);
+        let model = config.value_model(&subnets).unwrap();
+        let mut maximizer = Maximizer::new(config.state(), instance.nodes.clone(), instance.relationships.clone(),
+                                           algorithm_from_spec("simple", "Arkad".to_string()).unwrap(), subnets, model);
+        maximizer.verbose = false;
+        maximizer.collect();
+        assert_eq!(score, maximizer.state.score);
+        assert!(maximizer.state.elapsed <= 300);
+
+        assert_eq!(run(&instance, "simple", &RunConfig::default()).err(), Some("arkad has no start company Neo4j".to_string()));
+    }
+
+    #[test]
+    fn solves_and_verifies_under_the_configured_rules() {
+        let (nodes, relationships) = generate(&GeneratorConfig { nodes: 15, ..GeneratorConfig::default() });
+        let instance = Instance { name: "small".to_string(), nodes, relationships };
+        let mut scores = vec![];
+        for rules in [Rules::legacy(), Rules::official()] {
+            let config = RunConfig { budget: 500, rules, ..RunConfig::default() };
+            let (score, _, _) = run(&instance, "depth:20", &config).unwrap();
+            let subnets = Subnets::new(&instance.relationships, &instance.nodes);
+            let mut maximizer = Maximizer::new(config.state(), instance.nodes.clone(), instance.relationships.clone(),
+                                               algorithm_from_spec("depth:20", "Neo4j".to_string()).unwrap(), subnets, Box::new(Additive {}));
+            maximizer.verbose = false;
+            maximizer.collect();
+            assert_eq!(score, maximizer.state.score);
+            // The verifier agrees under the same rules.
+            let steps = route::replay(&maximizer.state.last_companies, config.state(), &instance.nodes,
+                                      &instance.relationships, &Additive {}).unwrap();
+            assert_eq!(steps.last().unwrap().score, score);
+            scores.push(score);
+        }
+        // Revisiting freely reaches more of this graph in the time.
+        assert!(scores[1] > scores[0]);
+    }
+}
@@ -0,0 +1,322 @@
+use serde_json::{json, Value};
+
+use crate::cli::Args;
+use crate::formats::PropertyNames;
+use crate::route;
+use crate::rules::Rules;
+use crate::state::State;
+use crate::subnets::Subnets;
+use crate::value::{self, ValueModel};
+use crate::{MONTE_CARLO_RUNS, MONTE_CARLO_SEED, TTL_PER_ITERATION};
+
+/// Everything that decides the outcome of a run, loaded from `--config run.json` with command
+/// line options taking precedence, and written next to the results to reproduce them.
+pub struct RunConfig {
+    pub data: String,
+    pub format: Option<String>,
+    pub edges: Option<String>,
+    pub bolt: Option<String>,
+    pub resume: Option<String>, // A route saved earlier to carry on from.
+    pub names: PropertyNames,
+    pub start: String,
+    pub budget: i64,
+    pub ttl: i32,
+    pub rules: Rules,
+    pub value: String,
+    pub diminishing_factor: f64,
+    pub set_bonus: Option<i64>,
+    pub algorithm: String,
+    pub quantile: Option<f64>, // Plan against this quantile of uncertain collection times, None on timePrice.
+    pub polish: bool, // Reorder the collections of the route found and add more with the time saved.
+    pub seed: u64,
+    pub monte_carlo_runs: usize,
+    pub route_out: Option<String>,
+    pub explain: bool, // Print why each step was taken and add it to the route.
+    pub out: Option<String>,
+}
+
+impl Default for RunConfig {
+    fn default() -> Self {
+        let state = State::new(TTL_PER_ITERATION);
+        RunConfig {
+            data: "data.json".to_string(),
+            format: None,
+            edges: None,
+            bolt: None,
+            resume: None,
+            names: PropertyNames::default(),
+            start: state.current_company,
+            budget: state.time_left,
+            ttl: state.ttl,
+            rules: state.rules,
+            value: "additive".to_string(),
+            diminishing_factor: 0.5,
+            set_bonus: None,
+            algorithm: "depth".to_string(),
+            quantile: None,
+            polish: false,
+            seed: MONTE_CARLO_SEED,
+            monte_carlo_runs: MONTE_CARLO_RUNS,
+            route_out: None,
+            explain: false,
+            out: None,
+        }
+    }
+}
+
+fn string(json: &Value, key: &str) -> Result<Option<String>, String> {
+    match &json[key] {
+        Value::Null => Ok(None),
+        Value::String(s) => Ok(Some(s.clone())),
+        other => Err(format!("{} should be a string, not {}", key, other)),
+    }
+}
+
+fn number<T: TryFrom<i64>>(json: &Value, key: &str) -> Result<Option<T>, String> {
+    match &json[key] {
+        Value::Null => Ok(None),
+        other => other.as_i64().and_then(|n| T::try_from(n).ok()).map(Some)
+            .ok_or(format!("{} should be a whole number, not {}", key, other)),
+    }
+}
+
+fn float(json: &Value, key: &str) -> Result<Option<f64>, String> {
+    match &json[key] {
+        Value::Null => Ok(None),
+        other => other.as_f64().map(Some).ok_or(format!("{} should be a number, not {}", key, other)),
+    }
+}
+
+fn boolean(json: &Value, key: &str) -> Result<Option<bool>, String> {
+    match &json[key] {
+        Value::Null => Ok(None),
+        other => other.as_bool().map(Some).ok_or(format!("{} should be true or false, not {}", key, other)),
+    }
+}
+
+/// Each further company of a subnet is worth `factor` of the one before, so it has to be in (0, 1].
+fn diminishing_factor(factor: f64) -> Result<f64, String> {
+    if factor > 0.0 && factor <= 1.0 {
+        Ok(factor)
+    } else {
+        Err(format!("The diminishing factor should be above 0 and at most 1, not {}", factor))
+    }
+}
+
+impl RunConfig {
+    /// Reads a config written by `to_json` (or by `tune`), missing entries keep their defaults.
+    pub fn from_json(json: &Value) -> Result<RunConfig, String> {
+        let mut config = RunConfig::default();
+        let input = &json["input"];
+        let properties = &input["properties"];
+        let objective = &json["objective"];
+        let outputs = &json["outputs"];
+        if let Some(data) = string(input, "data")? { config.data = data; }
+        config.format = string(input, "format")?;
+        config.edges = string(input, "edges")?;
+        config.bolt = string(input, "bolt")?;
+        config.resume = string(input, "resume")?;
+        if let Some(name) = string(properties, "name")? { config.names.name = name; }
+        if let Some(value) = string(properties, "value")? { config.names.value = value; }
+        if let Some(cost) = string(properties, "cost")? { config.names.cost = cost; }
+        if let Some(path_cost) = string(properties, "path_cost")? { config.names.path_cost = path_cost; }
+        config.names.path_type = string(properties, "path_type")?;
+        if let Some(start) = string(json, "start")? { config.start = start; }
+        if let Some(budget) = number(json, "budget")? { config.budget = budget; }
+        if let Some(ttl) = number(json, "ttl")? { config.ttl = ttl; }
+        if let Some(rules) = string(json, "rules")? { config.rules = rules.parse()?; }
+        if let Some(value) = string(objective, "value")? { config.value = value; }
+        if let Some(factor) = float(objective, "diminishing_factor")? { config.diminishing_factor = diminishing_factor(factor)?; }
+        config.set_bonus = number(objective, "set_bonus")?;
+        if let Some(algorithm) = string(json, "algorithm")? { config.algorithm = algorithm; }
+        config.quantile = float(json, "quantile")?;
+        if let Some(polish) = boolean(json, "polish")? { config.polish = polish; }
+        if let Some(seed) = number(json, "seed")? { config.seed = seed; }
+        if let Some(runs) = number(json, "monte_carlo_runs")? { config.monte_carlo_runs = runs; }
+        config.route_out = string(outputs, "route")?;
+        if let Some(explain) = boolean(outputs, "explain")? { config.explain = explain; }
+        config.out = string(outputs, "out")?;
+        Ok(config)
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "input": {
+                "data": self.data,
+                "format": self.format,
+                "edges": self.edges,
+                "bolt": self.bolt,
+                "resume": self.resume,
+                "properties": {
+                    "name": self.names.name,
+                    "value": self.names.value,
+                    "cost": self.names.cost,
+                    "path_cost": self.names.path_cost,
+                    "path_type": self.names.path_type,
+                },
+            },
+            "start": self.start,
+            "budget": self.budget,
+            "ttl": self.ttl,
+            "rules": self.rules.to_string(),
+            "objective": {
+                "value": self.value,
+                "diminishing_factor": self.diminishing_factor,
+                "set_bonus": self.set_bonus,
+            },
+            "algorithm": self.algorithm,
+            "quantile": self.quantile,
+            "polish": self.polish,
+            "seed": self.seed,
+            "monte_carlo_runs": self.monte_carlo_runs,
+            "outputs": {
+                "route": self.route_out,
+                "explain": self.explain,
+                "out": self.out,
+            },
+        })
+    }
+
+    /// The config of `--config` if given, otherwise the defaults, overridden by the options.
+    pub fn from_args(args: &Args) -> Result<RunConfig, String> {
+        let mut config = match args.get("config") {
+            Some(path) => {
+                let text = std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+                let json = serde_json::from_str(&text).map_err(|e| format!("Could not parse {}: {}", path, e))?;
+                RunConfig::from_json(&json)?
+            }
+            None => RunConfig::default(),
+        };
+        let text = |key: &str, field: &mut String| if let Some(v) = args.get(key) { *field = v.to_string(); };
+        let optional = |key: &str, field: &mut Option<String>| if let Some(v) = args.get(key) { *field = Some(v.to_string()); };
+        text("data", &mut config.data);
+        optional("format", &mut config.format);
+        optional("edges", &mut config.edges);
+        optional("bolt", &mut config.bolt);
+        optional("resume", &mut config.resume);
+        text("name-property", &mut config.names.name);
+        text("value-property", &mut config.names.value);
+        text("cost-property", &mut config.names.cost);
+        text("path-cost-property", &mut config.names.path_cost);
+        optional("path-type", &mut config.names.path_type);
+        text("start", &mut config.start);
+        config.budget = args.get_or("budget", config.budget);
+        // --ttl is taken by the tuner for the look ahead ttl.
+        config.ttl = args.get_or("walk-ttl", config.ttl);
+        if let Some(rules) = args.get("rules") {
+            config.rules = rules.parse()?;
+        }
+        text("value", &mut config.value);
+        if let Some(factor) = args.get("diminishing-factor") {
+            let factor = factor.parse().map_err(|_| "--diminishing-factor takes a factor such as 0.5")?;
+            config.diminishing_factor = diminishing_factor(factor)?;
+        }
+        if let Some(bonus) = args.get("set-bonus") {
+            config.set_bonus = Some(bonus.parse().map_err(|_| "--set-bonus takes the bonus for completing a subnet")?);
+        }
+        text("algorithm", &mut config.algorithm);
+        if let Some(depth) = args.get("depth") {
+            // The old --depth option, only meaningful for the depth search.
+            if config.algorithm == "depth" {
+                config.algorithm = format!("depth:{}", depth);
+            }
+        }
+        if let Some(quantile) = args.get("quantile") {
+            config.quantile = Some(quantile.parse().map_err(|_| "--quantile takes a probability such as 0.9")?);
+        }
+        if config.quantile.is_some_and(|q| !(0.0..=1.0).contains(&q)) {
+            return Err("The quantile should be between 0 and 1.".to_string());
+        }
+        config.polish = args.get_or("polish", config.polish);
+        config.seed = args.get_or("seed", config.seed);
+        config.monte_carlo_runs = args.get_or("monte-carlo-runs", config.monte_carlo_runs);
+        optional("route-out", &mut config.route_out);
+        config.explain = args.get_or("explain", config.explain);
+        optional("out", &mut config.out);
+        Ok(config)
+    }
+
+    pub fn state(&self) -> State {
+        State::starting(self.start.clone(), self.budget, self.ttl).with_rules(self.rules)
+    }
+
+    /// The objective, set bonuses are paid for completing a subnet.
+    pub fn value_model<K>(&self, subnets: &Subnets<K>) -> Result<Box<dyn ValueModel>, String> {
+        let model: Box<dyn ValueModel> = match self.value.as_str() {
+            "additive" => Box::new(value::Additive {}),
+            "diminishing" => Box::new(value::DiminishingReturns { factor: self.diminishing_factor }),
+            other => return Err(format!("Unknown value model {}, expected additive or diminishing", other)),
+        };
+        Ok(match self.set_bonus {
+            Some(bonus) => Box::new(value::SetBonus::from_subnets(model, subnets, bonus)),
+            None => model,
+        })
+    }
+
+    /// Where solving starts, the end of the `resume` route if given, which carries on by the
+    /// rules it was saved with.
+    pub fn start_state(&self) -> Result<State, String> {
+        match &self.resume {
+            Some(path) => {
+                let text = std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+                let json = serde_json::from_str(&text).map_err(|e| format!("Could not parse {}: {}", path, e))?;
+                route::state_from_json(&json)
+            }
+            None => Ok(self.state()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_and_options_win() {
+        let mut config = RunConfig { start: "Arkad".to_string(), budget: 300, set_bonus: Some(50),
+                                     rules: Rules::official(), polish: true,
+                                     quantile: Some(0.9), ..RunConfig::default() };
+        config.names.path_type = Some("PATH".to_string());
+        let json = config.to_json();
+        assert_eq!(RunConfig::from_json(&json).unwrap().to_json(), json);
+
+        // A tuner result only names the algorithm.
+        let tuned = RunConfig::from_json(&json!({"algorithm": "depth:60", "training_score": 1.0})).unwrap();
+        assert_eq!(tuned.algorithm, "depth:60");
+        assert_eq!(tuned.budget, 4500);
+        assert!(RunConfig::from_json(&json!({"budget": "long"})).is_err());
+        for wrong in [json!({"seed": "7"}), json!({"seed": -1}), json!({"polish": "yes"}),
+                      json!({"outputs": {"explain": 1}}), json!({"objective": {"diminishing_factor": "0.5"}})] {
+            assert!(RunConfig::from_json(&wrong).is_err(), "{} was read", wrong);
+        }
+        assert_eq!(RunConfig::from_json(&json!({"seed": 7})).unwrap().seed, 7);
+
+        let args = Args::parse(["solve", "--budget", "100", "--depth", "8"].iter().map(|s| s.to_string()));
+        let config = RunConfig::from_args(&args).unwrap();
+        assert_eq!((config.budget, config.algorithm.as_str()), (100, "depth:8"));
+        assert_eq!(config.state().time_left, 100);
+
+        let args = Args::parse(["solve", "--quantile", "0.8"].iter().map(|s| s.to_string()));
+        assert_eq!(RunConfig::from_args(&args).unwrap().quantile, Some(0.8));
+        let args = Args::parse(["solve", "--quantile", "80"].iter().map(|s| s.to_string()));
+        assert!(RunConfig::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn diminishing_factors_are_in_zero_to_one() {
+        let factor = |factor: f64| RunConfig::from_json(&json!({"objective": {"diminishing_factor": factor}}))
+            .map(|config| config.diminishing_factor);
+        assert_eq!(factor(1.0), Ok(1.0));
+        assert_eq!(factor(0.25), Ok(0.25));
+        assert!(factor(0.0).is_err());
+        assert!(factor(1.5).is_err());
+        assert!(factor(-0.5).is_err());
+
+        let factor = |factor: &str| RunConfig::from_args(&Args::parse(["solve", "--diminishing-factor", factor]
+            .iter().map(|s| s.to_string()))).map(|config| config.diminishing_factor);
+        assert_eq!(factor("0.5"), Ok(0.5));
+        assert_eq!(factor("NaN"), Err("The diminishing factor should be above 0 and at most 1, not NaN".to_string()));
+        assert!(factor("2").is_err());
+        assert!(factor("half").is_err());
+    }
+}
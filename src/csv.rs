@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use serde_json::{Map, Value};
+
+use crate::{Node, Relationships};
+use crate::route::Step;
+use crate::formats::{build, node_from_properties, optional_properties, sorted_names, PropertyNames};
+
+/// Splits CSV text into rows of fields, quoted fields may hold commas, quotes and newlines.
+fn parse(text: &str) -> Vec<Vec<String>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            '\r' if !quoted => {}
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows.retain(|r| !(r.len() == 1 && r[0].trim().is_empty()));
+    rows
+}
+
+pub(crate) fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Rows as property maps keyed by the header, the `numeric` columns become numbers where they
+/// parse as one, so ids and names like "007" stay as they are.
+fn records(text: &str, numeric: &[&str]) -> Result<Vec<Map<String, Value>>, String> {
+    let mut rows = parse(text).into_iter();
+    let header = rows.next().ok_or("CSV file without header.")?;
+    Ok(rows.map(|row| header.iter().zip(row).filter(|(_, v)| !v.is_empty()).map(|(k, v)| {
+        let k = k.trim();
+        let value = if numeric.contains(&k) {
+            v.trim().parse::<i64>().map(Value::from).unwrap_or(Value::String(v))
+        } else {
+            Value::String(v)
+        };
+        (k.to_string(), value)
+    }).collect()).collect())
+}
+
+fn text_of(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+/// Reads a nodes CSV (`id,name,swag,timePrice`, optionally `earliest`, `latest`, `category` and
+/// `timePriceDistribution` as JSON) and an edges CSV (`from,to,timePrice`), where `from` and `to` are node ids.
+pub fn csv_to_structures(nodes_text: &str, edges_text: &str, names: &PropertyNames) -> Result<(HashMap<String, Node>, Relationships), String> {
+    let mut nodes = vec![];
+    let numeric = [names.value.as_str(), names.cost.as_str(), "earliest", "latest"];
+    for (i, record) in records(nodes_text, &numeric)?.iter().enumerate() {
+        let id = text_of(record.get("id")).or(text_of(record.get(&names.name)))
+            .ok_or(format!("Node row {} has neither id nor {}.", i + 1, names.name))?;
+        let node = node_from_properties(&id, record, names)
+            .ok_or(format!("Node {} lacks {} or {}.", id, names.value, names.cost))?;
+        nodes.push((id, node));
+    }
+    let mut edges = vec![];
+    for (i, record) in records(edges_text, &[names.path_cost.as_str()])?.iter().enumerate() {
+        let from = text_of(record.get("from")).ok_or(format!("Edge row {} has no from.", i + 1))?;
+        let to = text_of(record.get("to")).ok_or(format!("Edge row {} has no to.", i + 1))?;
+        let cost = record.get(&names.path_cost).and_then(|c| c.as_i64())
+            .ok_or(format!("Edge row {} has no {}.", i + 1, names.path_cost))?;
+        edges.push((from, to, cost));
+    }
+    build(nodes, edges)
+}
+
+/// The nodes and edges CSV files, companies are their own ids.
+pub fn structures_to_csv(nodes: &HashMap<String, Node>, relationships: &Relationships, names: &PropertyNames) -> (String, String) {
+    let mut nodes_csv = String::new();
+    writeln!(nodes_csv, "id,{},{},{},earliest,latest,category,timePriceDistribution", names.name, names.value, names.cost).unwrap();
+    let mut edges_csv = String::new();
+    writeln!(edges_csv, "from,to,{}", names.path_cost).unwrap();
+    for name in sorted_names(nodes) {
+        let node = &nodes[name];
+        let extra = optional_properties(node);
+        let field = |key: &str| text_of(extra.get(key)).map(|v| quote(&v)).unwrap_or_default();
+        writeln!(nodes_csv, "{},{},{},{},{},{},{},{}", quote(name), quote(name), node.value, node.cost,
+                 field("earliest"), field("latest"), field("category"), field("timePriceDistribution")).unwrap();
+        for path in relationships.get(name).iter().flat_map(|rel| &rel.paths) {
+            writeln!(edges_csv, "{},{},{}", quote(name), quote(&path.to), path.cost).unwrap();
+        }
+    }
+    (nodes_csv, edges_csv)
+}
+
+/// One row per step of a replayed route, `collected` is whether swag was actually collected.
+pub fn route_to_csv(steps: &[Step]) -> String {
+    let mut route_csv = String::new();
+    writeln!(route_csv, "step,company,collected,travel,time_left,score").unwrap();
+    for (i, step) in steps.iter().enumerate() {
+        writeln!(route_csv, "{},{},{},{},{},{}", i, quote(&step.company), step.collected,
+                 step.travel, step.time_left, step.score).unwrap();
+    }
+    route_csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_quoted_fields_and_round_trips() {
+        let nodes_csv = "id,name,swag,timePrice\nc0,\"Big, \"\"Corp\"\"\",100,10\nc1,Dovu,129,27\n";
+        let edges_csv = "from,to,timePrice\r\nc0,c1,5\r\nc1,c0,6\r\n";
+        let names = PropertyNames::default();
+        let (nodes, relationships) = csv_to_structures(nodes_csv, edges_csv, &names).unwrap();
+        assert_eq!(nodes["Big, \"Corp\""].value, 100);
+        assert_eq!(relationships["Dovu"].paths[0].to, "Big, \"Corp\"");
+
+        let (nodes_out, edges_out) = structures_to_csv(&nodes, &relationships, &names);
+        let (again, again_relationships) = csv_to_structures(&nodes_out, &edges_out, &names).unwrap();
+        assert_eq!(again.len(), 2);
+        assert_eq!(again_relationships["Big, \"Corp\""].paths[0].cost, 5);
+    }
+
+    #[test]
+    fn keeps_ids_and_names_which_look_like_numbers() {
+        let nodes_csv = "id,name,swag,timePrice,earliest\n007,007,5,1,30\n10,Ten,3,2,\n";
+        let edges_csv = "from,to,timePrice\n007,10,4\n";
+        let (nodes, relationships) = csv_to_structures(nodes_csv, edges_csv, &PropertyNames::default()).unwrap();
+        assert_eq!(nodes["007"].window.as_ref().unwrap().earliest, 30);
+        assert_eq!(relationships["007"].paths[0].to, "Ten");
+    }
+}
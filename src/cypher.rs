@@ -0,0 +1,665 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use serde_json::{Map, Value};
+
+use crate::{Node, Relationships};
+use crate::route::Step;
+use crate::formats::{build, node_from_properties, optional_properties, sorted_names, PropertyNames};
+
+/// `(var:Label {props})` in a pattern.
+struct NodePattern {
+    variable: Option<String>,
+    properties: Map<String, Value>,
+}
+
+/// `-[:TYPE {props}]->` in a pattern, `forward` is false for `<-[...]-`.
+struct RelationshipPattern {
+    kind: Option<String>,
+    properties: Map<String, Value>,
+    forward: bool,
+}
+
+/// The property `apoc.export.cypher.*` identifies nodes by while importing.
+const IMPORT_ID: &str = "UNIQUE IMPORT ID";
+
+/// The key of a company by its import id, else its name.
+fn company_key(properties: &Map<String, Value>, names: &PropertyNames) -> Option<String> {
+    match properties.get(IMPORT_ID) {
+        Some(id) => Some(format!("{} {}", IMPORT_ID, id)),
+        None => properties.get(&names.name).and_then(|n| n.as_str()).map(|n| n.to_string()),
+    }
+}
+
+/// A map unwound by apoc, with its `_id` under the import id property.
+fn imported(map: &Map<String, Value>) -> Map<String, Value> {
+    let mut map = map.clone();
+    if let Some(id) = map.remove("_id") {
+        map.insert(IMPORT_ID.to_string(), id);
+    }
+    map
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    names: &'a PropertyNames,
+    // Companies by the name in their properties, in the order they were created.
+    nodes: Vec<(String, Node)>,
+    edges: Vec<(String, String, i64)>,
+    // Variables of the current statement, bound to company keys.
+    variables: HashMap<String, String>,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() {
+                self.pos += 1;
+            } else if c == '/' && self.chars.get(self.pos + 1) == Some(&'/') {
+                while self.peek().is_some_and(|c| c != '\n') {
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn eat(&mut self, expected: &str) -> bool {
+        self.skip_whitespace();
+        let end = self.pos + expected.chars().count();
+        if end <= self.chars.len() && self.chars[self.pos..end].iter().copied().eq(expected.chars()) {
+            self.pos = end;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(format!("Expected {} at character {}.", expected, self.pos))
+        }
+    }
+
+    fn identifier(&mut self) -> Option<String> {
+        self.skip_whitespace();
+        if self.peek() == Some('`') {
+            // A doubled backtick inside stands for one.
+            self.pos += 1;
+            let mut identifier = String::new();
+            while let Some(c) = self.peek() {
+                self.pos += 1;
+                if c != '`' {
+                    identifier.push(c);
+                } else if self.peek() == Some('`') {
+                    identifier.push('`');
+                    self.pos += 1;
+                } else {
+                    break;
+                }
+            }
+            return Some(identifier);
+        }
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            self.pos += 1;
+        }
+        if start == self.pos { None } else { Some(self.chars[start..self.pos].iter().collect()) }
+    }
+
+    fn string(&mut self, quote: char) -> Result<String, String> {
+        self.pos += 1;
+        let mut result = String::new();
+        loop {
+            match self.peek() {
+                None => return Err("Unterminated string.".to_string()),
+                Some('\\') => {
+                    result.push(*self.chars.get(self.pos + 1).ok_or("Unterminated string.")?);
+                    self.pos += 2;
+                }
+                Some(c) if c == quote => {
+                    self.pos += 1;
+                    return Ok(result);
+                }
+                Some(c) => {
+                    result.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(q) if q == '\'' || q == '"' => Ok(Value::String(self.string(q)?)),
+            Some('[') => {
+                self.pos += 1;
+                let mut items = vec![];
+                while !self.eat("]") {
+                    items.push(self.value()?);
+                    self.eat(",");
+                }
+                Ok(Value::Array(items))
+            }
+            Some('{') => Ok(Value::Object(self.properties()?)),
+            _ => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '.' || c == '-' || c == '+') {
+                    self.pos += 1;
+                }
+                let literal: String = self.chars[start..self.pos].iter().collect();
+                match literal.to_lowercase().as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    "null" => Ok(Value::Null),
+                    _ => literal.parse::<i64>().map(Value::from)
+                        .or_else(|_| literal.parse::<f64>().map(Value::from))
+                        .map_err(|_| format!("Unsupported value {} at character {}.", literal, start)),
+                }
+            }
+        }
+    }
+
+    fn properties(&mut self) -> Result<Map<String, Value>, String> {
+        let mut properties = Map::new();
+        if !self.eat("{") {
+            return Ok(properties);
+        }
+        while !self.eat("}") {
+            let key = self.identifier().ok_or(format!("Expected a property name at character {}.", self.pos))?;
+            self.expect(":")?;
+            let value = self.value()?;
+            properties.insert(key, value);
+            self.eat(",");
+        }
+        Ok(properties)
+    }
+
+    fn node_pattern(&mut self) -> Result<NodePattern, String> {
+        self.expect("(")?;
+        let variable = self.identifier();
+        while self.eat(":") {
+            self.identifier();
+        }
+        let properties = self.properties()?;
+        self.expect(")")?;
+        Ok(NodePattern { variable, properties })
+    }
+
+    fn relationship_pattern(&mut self) -> Result<Option<RelationshipPattern>, String> {
+        let backward = if self.eat("<-") {
+            true
+        } else if self.eat("-") {
+            false
+        } else {
+            return Ok(None);
+        };
+        let mut kind = None;
+        let mut properties = Map::new();
+        if self.eat("[") {
+            self.identifier();
+            if self.eat(":") {
+                kind = self.identifier();
+            }
+            properties = self.properties()?;
+            self.expect("]")?;
+        }
+        if backward {
+            self.expect("-")?;
+        } else {
+            self.expect("->")?;
+        }
+        Ok(Some(RelationshipPattern { kind, properties, forward: !backward }))
+    }
+
+    /// The company key of a node pattern, creating the company if the pattern describes one.
+    fn resolve(&mut self, pattern: NodePattern) -> Result<Option<String>, String> {
+        let key = match company_key(&pattern.properties, self.names) {
+            Some(key) => Some(key),
+            None => pattern.variable.as_ref().and_then(|v| self.variables.get(v)).cloned(),
+        };
+        if let Some(key) = &key {
+            self.add_company(key, &pattern.properties);
+            if let Some(variable) = pattern.variable {
+                self.variables.insert(variable, key.clone());
+            }
+        }
+        Ok(key)
+    }
+
+    /// Whether a pattern follows, `CREATE CONSTRAINT` and `CREATE INDEX` are skipped.
+    fn starts_pattern(&mut self) -> bool {
+        self.skip_whitespace();
+        self.peek() == Some('(')
+    }
+
+    fn add_company(&mut self, key: &str, properties: &Map<String, Value>) {
+        if !self.nodes.iter().any(|(k, _)| k == key) {
+            if let Some(node) = node_from_properties(key, properties, self.names) {
+                self.nodes.push((key.to_string(), node));
+            }
+        }
+    }
+
+    /// The text up to the end of the statement, leaving the `;`.
+    fn rest_of_statement(&mut self) -> Result<String, String> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            match c {
+                ';' => break,
+                '\'' | '"' => {
+                    self.string(c)?;
+                }
+                '`' => {
+                    self.identifier();
+                }
+                _ => self.pos += 1,
+            }
+        }
+        Ok(self.chars[start..self.pos].iter().collect())
+    }
+
+    /// `UNWIND [rows] AS row` as `apoc.export.cypher.*` writes it, creating a company from each
+    /// `{_id, properties}` row or a path from each `{start, end, properties}` row.
+    fn unwind(&mut self) -> Result<(), String> {
+        let rows = match self.value()? {
+            Value::Array(rows) => rows,
+            _ => return Err(format!("Expected a list to unwind at character {}.", self.pos)),
+        };
+        let at = self.pos;
+        if !self.identifier().is_some_and(|w| w.eq_ignore_ascii_case("AS")) || self.identifier().is_none() {
+            return Err(format!("Expected AS and a variable at character {}.", at));
+        }
+        let statement = self.rest_of_statement()?;
+        let kind = statement.split_once("-[").and_then(|(_, r)| r.split_once(']'))
+            .and_then(|(r, _)| r.split_once(':'))
+            .map(|(_, kind)| kind.split(|c: char| c == '{' || c.is_whitespace()).find(|k| !k.is_empty()).unwrap_or("").trim_matches('`').to_string());
+        for row in &rows {
+            let row = imported(row.as_object().ok_or("Expected a map in the unwound list.")?);
+            let mut properties = row.get("properties").and_then(|p| p.as_object()).map(imported).unwrap_or_default();
+            match &kind {
+                None => {
+                    properties.extend(row.iter().filter(|(k, _)| *k != "properties").map(|(k, v)| (k.clone(), v.clone())));
+                    let key = company_key(&properties, self.names)
+                        .ok_or(format!("Unwound company without {} or {} in {}.", IMPORT_ID, self.names.name, Value::from(row.clone())))?;
+                    self.add_company(&key, &properties);
+                }
+                Some(kind) => {
+                    if self.names.path_type.as_ref().is_some_and(|t| t != kind) {
+                        continue;
+                    }
+                    let end = |side: &str| row.get(side).and_then(|s| s.as_object()).and_then(|s| company_key(&imported(s), self.names))
+                        .ok_or(format!("Unwound path without a {} company in {}.", side, Value::from(row.clone())));
+                    let (from, to) = (end("start")?, end("end")?);
+                    if let Some(cost) = properties.get(&self.names.path_cost).and_then(|c| c.as_i64()) {
+                        self.edges.push((from, to, cost));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn path_pattern(&mut self) -> Result<(), String> {
+        let first = self.node_pattern()?;
+        let mut previous = self.resolve(first)?;
+        while let Some(relationship) = self.relationship_pattern()? {
+            let next = self.node_pattern()?;
+            let next = self.resolve(next)?;
+            let wanted = self.names.path_type.is_none() || relationship.kind == self.names.path_type;
+            let cost = relationship.properties.get(&self.names.path_cost).and_then(|c| c.as_i64());
+            if let (true, Some(from), Some(to), Some(cost)) = (wanted, &previous, &next, cost) {
+                if relationship.forward {
+                    self.edges.push((from.clone(), to.clone(), cost));
+                } else {
+                    self.edges.push((to.clone(), from.clone(), cost));
+                }
+            }
+            previous = next;
+        }
+        Ok(())
+    }
+
+    fn script(&mut self) -> Result<(), String> {
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None => return Ok(()),
+                Some(';') => {
+                    self.pos += 1;
+                    self.variables.clear();
+                }
+                Some(q) if q == '\'' || q == '"' => {
+                    self.string(q)?;
+                }
+                Some(c) if c.is_alphabetic() => {
+                    let word = self.identifier().unwrap().to_uppercase();
+                    if word == "UNWIND" {
+                        self.unwind()?;
+                    } else if (word == "CREATE" || word == "MATCH" || word == "MERGE") && self.starts_pattern() {
+                        self.path_pattern()?;
+                        while self.eat(",") {
+                            self.path_pattern()?;
+                        }
+                    }
+                }
+                Some(_) => self.pos += 1,
+            }
+        }
+    }
+}
+
+/// Reads a Cypher script of `CREATE`, `MATCH` and `MERGE` patterns such as
+/// `CREATE (a:Company {name: 'Neo4j', swag: 350, timePrice: 147})-[:PATH {timePrice: 24}]->(b)`,
+/// or the `UNWIND` batches of `apoc.export.cypher.*`. Companies are identified by their
+/// `UNIQUE IMPORT ID` if they have one, else by their name property, variables hold until the next `;`.
+pub fn cypher_to_structures(text: &str, names: &PropertyNames) -> Result<(HashMap<String, Node>, Relationships), String> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        pos: 0,
+        names,
+        nodes: vec![],
+        edges: vec![],
+        variables: HashMap::new(),
+    };
+    parser.script()?;
+    let companies: HashSet<&String> = parser.nodes.iter().map(|(k, _)| k).collect();
+    let edges = parser.edges.iter()
+        .filter(|(from, to, _)| companies.contains(from) && companies.contains(to))
+        .cloned().collect();
+    build(parser.nodes, edges)
+}
+
+/// A Cypher literal for a property value.
+fn literal(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'")),
+        Value::Array(items) => format!("[{}]", items.iter().map(literal).collect::<Vec<_>>().join(", ")),
+        Value::Object(map) => properties_literal(map),
+        other => other.to_string(),
+    }
+}
+
+/// Property names which are not plain identifiers are quoted with backticks.
+fn key(name: &str) -> String {
+    if name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        name.to_string()
+    } else {
+        format!("`{}`", name.replace('`', "``"))
+    }
+}
+
+fn properties_literal(map: &Map<String, Value>) -> String {
+    format!("{{{}}}", map.iter().map(|(k, v)| format!("{}: {}", key(k), literal(v))).collect::<Vec<_>>().join(", "))
+}
+
+/// The properties of a company node.
+pub fn company_properties(name: &str, node: &Node, names: &PropertyNames) -> String {
+    let mut properties = Map::new();
+    properties.insert(names.name.clone(), Value::from(name));
+    properties.insert(names.value.clone(), Value::from(node.value));
+    properties.insert(names.cost.clone(), Value::from(node.cost));
+    properties.extend(optional_properties(node));
+    properties_literal(&properties)
+}
+
+/// A `MATCH` of a company by name.
+pub fn match_company(variable: &str, name: &str, names: &PropertyNames) -> String {
+    format!("({}:Company {{{}: {}}})", variable, key(&names.name), literal(&Value::from(name)))
+}
+
+/// A script creating `:Company` nodes and the paths between them, readable by
+/// `cypher_to_structures` and runnable in cypher-shell.
+pub fn structures_to_cypher(nodes: &HashMap<String, Node>, relationships: &Relationships, names: &PropertyNames) -> String {
+    let mut script = String::new();
+    let path_type = names.path_type.as_deref().unwrap_or("PATH");
+    for name in sorted_names(nodes) {
+        writeln!(script, "CREATE (:Company {});", company_properties(name, &nodes[name], names)).unwrap();
+    }
+    for name in sorted_names(nodes) {
+        for path in relationships.get(name).iter().flat_map(|rel| &rel.paths) {
+            writeln!(script, "MATCH {}, {} CREATE (a)-[:{} {{{}: {}}}]->(b);",
+                     match_company("a", name, names), match_company("b", &path.to, names),
+                     key(path_type), key(&names.path_cost), path.cost).unwrap();
+        }
+    }
+    script
+}
+
+/// A `:Route` with an ordered `:VISITED {step, collected}` relationship to every company on it.
+/// Existing routes with the same name are replaced.
+pub fn route_to_cypher(route_name: &str, steps: &[Step], names: &PropertyNames) -> String {
+    let mut script = String::new();
+    let route = literal(&Value::from(route_name));
+    let last = steps.last();
+    writeln!(script, "MATCH (old:Route {{name: {}}}) DETACH DELETE old;", route).unwrap();
+    writeln!(script, "CREATE (:Route {{name: {}, score: {}, timeLeft: {}, steps: {}}});", route,
+             last.map_or(0, |s| s.score), last.map_or(0, |s| s.time_left), steps.len()).unwrap();
+    for (i, step) in steps.iter().enumerate() {
+        writeln!(script, "MATCH (r:Route {{name: {}}}), {} CREATE (r)-[:VISITED {{step: {}, collected: {}, timeLeft: {}, score: {}}}]->(c);",
+                 route, match_company("c", &step.company, names), i, step.collected, step.time_left, step.score).unwrap();
+    }
+    script
+}
+
+/// The files written for `LOAD CSV`, they must be copied to the import directory of Neo4j.
+pub struct CsvFiles {
+    pub(crate) nodes: String,
+    pub(crate) edges: String,
+    pub(crate) route: String,
+}
+
+impl CsvFiles {
+    pub fn new(prefix: &str) -> CsvFiles {
+        CsvFiles {
+            nodes: format!("{}_nodes.csv", prefix),
+            edges: format!("{}_edges.csv", prefix),
+            route: format!("{}_route.csv", prefix),
+        }
+    }
+}
+
+fn file_url(path: &str) -> String {
+    let file = std::path::Path::new(path).file_name().map_or(path.to_string(), |f| f.to_string_lossy().to_string());
+    literal(&Value::from(format!("file:///{}", file)))
+}
+
+/// `LOAD CSV` statements creating the same graph and route as structures_to_cypher and
+/// route_to_cypher, from the files of structures_to_csv and route_to_csv.
+pub fn load_csv_script(files: &CsvFiles, route_name: Option<&str>, names: &PropertyNames) -> String {
+    let mut script = String::new();
+    let (name, value, cost, path_cost) = (key(&names.name), key(&names.value), key(&names.cost), key(&names.path_cost));
+    writeln!(script, "LOAD CSV WITH HEADERS FROM {} AS row", file_url(&files.nodes)).unwrap();
+    writeln!(script, "CREATE (:Company {{{}: row.{}, {}: toInteger(row.{}), {}: toInteger(row.{})}});",
+             name, name, value, value, cost, cost).unwrap();
+    writeln!(script, "LOAD CSV WITH HEADERS FROM {} AS row", file_url(&files.edges)).unwrap();
+    writeln!(script, "MATCH (a:Company {{{}: row.from}}), (b:Company {{{}: row.to}})", name, name).unwrap();
+    writeln!(script, "CREATE (a)-[:{} {{{}: toInteger(row.{})}}]->(b);",
+             key(names.path_type.as_deref().unwrap_or("PATH")), path_cost, path_cost).unwrap();
+    if let Some(route_name) = route_name {
+        let route = literal(&Value::from(route_name));
+        writeln!(script, "MATCH (old:Route {{name: {}}}) DETACH DELETE old;", route).unwrap();
+        writeln!(script, "CREATE (:Route {{name: {}}});", route).unwrap();
+        writeln!(script, "LOAD CSV WITH HEADERS FROM {} AS row", file_url(&files.route)).unwrap();
+        writeln!(script, "MATCH (r:Route {{name: {}}}), (c:Company {{{}: row.company}})", route, name).unwrap();
+        writeln!(script, "CREATE (r)-[:VISITED {{step: toInteger(row.step), collected: row.collected = 'true', timeLeft: toInteger(row.time_left), score: toInteger(row.score)}}]->(c);").unwrap();
+        writeln!(script, "MATCH (r:Route {{name: {}}})-[v:VISITED]->() WITH r, max(v.step) AS last, max(v.score) AS score", route).unwrap();
+        writeln!(script, "SET r.score = score, r.steps = last + 1;").unwrap();
+    }
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_create_and_match_statements() {
+        let script = "
+            // Two companies and a path each way
+            CREATE (a:Company {name: 'Neo4j', swag: 350, timePrice: 147}),
+                   (b:Company {name: \"Bad'Corp\", swag: -1, timePrice: 95})-[:PATH {timePrice: 7}]->(a);
+            MATCH (x:Company {name: 'Neo4j'}), (y:Company {name: \"Bad'Corp\"})
+            CREATE (x)-[:PATH {timePrice: 24}]->(y), (y)<-[:OTHER {timePrice: 1}]-(x);
+        ";
+        let names = PropertyNames { path_type: Some("PATH".to_string()), ..PropertyNames::default() };
+        let (nodes, relationships) = cypher_to_structures(script, &names).unwrap();
+
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes["Bad'Corp"].value, -1);
+        assert_eq!(relationships["Bad'Corp"].paths.len(), 1);
+        assert_eq!(relationships["Bad'Corp"].paths[0].cost, 7);
+        assert_eq!(relationships["Neo4j"].paths.len(), 1);
+        assert_eq!(relationships["Neo4j"].paths[0].to, "Bad'Corp");
+    }
+
+    #[test]
+    fn maps_configured_property_names() {
+        let script = "CREATE (:Booth {title: 'Neo4j', points: 3, queue: 2})-[:WALK {minutes: 4}]->(:Booth {title: 'Dovu', points: 1, queue: 1})";
+        let names = PropertyNames {
+            name: "title".to_string(),
+            value: "points".to_string(),
+            cost: "queue".to_string(),
+            path_cost: "minutes".to_string(),
+            path_type: None,
+        };
+        let (nodes, relationships) = cypher_to_structures(script, &names).unwrap();
+        assert_eq!(nodes["Dovu"].cost, 1);
+        assert_eq!(relationships["Neo4j"].paths[0].cost, 4);
+    }
+
+    #[test]
+    fn reads_backticks_doubled_in_quoted_identifiers() {
+        let script = "CREATE (`Bad``Corp`:Company {name: 'Bad`Corp', `swag`: 5, timePrice: 1}), \
+                      (`Neo``4j`:Company {name: 'Neo4j', swag: 0, timePrice: 0}), (`Neo``4j`)-[:PATH {timePrice: 3}]->(`Bad``Corp`);";
+        let (nodes, relationships) = cypher_to_structures(script, &PropertyNames::default()).unwrap();
+        assert_eq!(nodes["Bad`Corp"].value, 5);
+        assert_eq!(relationships["Neo4j"].paths[0].to, "Bad`Corp");
+
+        // Property names with a backtick are written doubled and read back.
+        let names = PropertyNames { name: "company`name".to_string(), ..PropertyNames::default() };
+        let script = structures_to_cypher(&nodes, &relationships, &names);
+        assert!(script.contains("`company``name`: 'Bad`Corp'"));
+        let (again, _) = cypher_to_structures(&script, &names).unwrap();
+        assert_eq!(again["Bad`Corp"].value, 5);
+    }
+
+    fn triangle() -> (HashMap<String, Node>, Relationships) {
+        let script = "CREATE (s:Company {name: 'Neo4j', swag: 0, timePrice: 0}), (a:Company {name: \"Bad'Corp\", swag: 100, timePrice: 10}), \
+                      (b:Company {name: 'Dovu', swag: 50, timePrice: 5}), (s)-[:PATH {timePrice: 20}]->(a), \
+                      (a)-[:PATH {timePrice: 15}]->(b), (b)-[:PATH {timePrice: 30}]->(s);";
+        cypher_to_structures(script, &PropertyNames::default()).unwrap()
+    }
+
+    #[test]
+    fn route_script_keeps_the_graph_and_lists_every_step() {
+        let (nodes, relationships) = triangle();
+        let names = PropertyNames::default();
+        let route: Vec<crate::Action> = [("Neo4j", false), ("Bad'Corp", true), ("Dovu", true)].iter()
+            .map(|(c, collected)| crate::Action::new(c.to_string(), *collected)).collect();
+        let steps = crate::route::replay(&route, crate::State::starting("Neo4j".to_string(), 100, 4),
+                                         &nodes, &relationships, &crate::value::Additive {}).unwrap();
+        let route_script = route_to_cypher("best", &steps, &names);
+        assert!(route_script.contains("CREATE (:Route {name: 'best', score: 150, timeLeft: 50, steps: 3});"));
+        let visited: Vec<&str> = route_script.lines().filter(|l| l.contains(":VISITED")).collect();
+        assert_eq!(visited.len(), 3);
+        assert!(visited[1].contains("{name: 'Bad\\'Corp'}") && visited[1].contains("{step: 1, collected: true, timeLeft: 70, score: 100}"));
+
+        // The route neither adds companies nor paths when the script is read back.
+        let script = structures_to_cypher(&nodes, &relationships, &names) + &route_script;
+        let (again, again_relationships) = cypher_to_structures(&script, &names).unwrap();
+        assert_eq!(again.len(), 3);
+        assert_eq!(again["Bad'Corp"].value, 100);
+        for name in ["Neo4j", "Bad'Corp", "Dovu"] {
+            let costs = |r: &Relationships| r[name].paths.iter().map(|p| (p.to.clone(), p.cost)).collect::<Vec<_>>();
+            assert_eq!(costs(&again_relationships), costs(&relationships));
+        }
+    }
+
+    #[test]
+    fn load_csv_script_reads_the_columns_of_the_exported_files() {
+        let names = PropertyNames {
+            name: "title".to_string(),
+            value: "points".to_string(),
+            cost: "queue".to_string(),
+            path_cost: "minutes".to_string(),
+            path_type: Some("WALK".to_string()),
+        };
+        let files = CsvFiles::new("out/fair");
+        let script = load_csv_script(&files, Some("best"), &names);
+        for file in ["file:///fair_nodes.csv", "file:///fair_edges.csv", "file:///fair_route.csv"] {
+            assert!(script.contains(file), "{} missing", file);
+        }
+        assert!(script.contains("CREATE (:Company {title: row.title, points: toInteger(row.points), queue: toInteger(row.queue)});"));
+        assert!(script.contains("CREATE (a)-[:WALK {minutes: toInteger(row.minutes)}]->(b);"));
+        assert!(!load_csv_script(&files, None, &names).contains(":Route"));
+
+        // Every column the script reads is written by the CSV export.
+        let (nodes, relationships) = triangle();
+        let (nodes_csv, edges_csv) = crate::csv::structures_to_csv(&nodes, &relationships, &names);
+        let route_csv = crate::csv::route_to_csv(&[]);
+        let mut columns: Vec<&str> = [&nodes_csv, &edges_csv, &route_csv].iter()
+            .flat_map(|csv| csv.lines().next().unwrap().split(',')).collect();
+        columns.sort();
+        for read in script.split("row.").skip(1) {
+            let column: String = read.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+            assert!(columns.binary_search(&column.as_str()).is_ok(), "column {} is not exported", column);
+        }
+    }
+
+    #[test]
+    fn reads_apoc_export_scripts() {
+        // apoc.export.cypher.all with the default cypher-shell format and UNWIND batches.
+        let batched = r#"
+            :begin
+            CREATE CONSTRAINT UNIQUE_IMPORT_NAME FOR (node:`UNIQUE IMPORT LABEL`) REQUIRE (node.`UNIQUE IMPORT ID`) IS UNIQUE;
+            :commit
+            :begin
+            UNWIND [{_id:0, properties:{name:"Neo4j", swag:350, timePrice:147}}, {_id:1, properties:{name:"Dovu", swag:129, timePrice:27}}] AS row
+            CREATE (n:`UNIQUE IMPORT LABEL`{`UNIQUE IMPORT ID`: row._id}) SET n += row.properties SET n:Company;
+            UNWIND [{_id:2, properties:{name:"best", score:129}}] AS row
+            CREATE (n:`UNIQUE IMPORT LABEL`{`UNIQUE IMPORT ID`: row._id}) SET n += row.properties SET n:Route;
+            :commit
+            :begin
+            UNWIND [{start: {_id:0}, end: {_id:1}, properties:{timePrice:24}}, {start: {_id:1}, end: {_id:0}, properties:{timePrice:25}}] AS row
+            MATCH (start:`UNIQUE IMPORT LABEL`{`UNIQUE IMPORT ID`: row.start._id})
+            MATCH (end:`UNIQUE IMPORT LABEL`{`UNIQUE IMPORT ID`: row.end._id})
+            CREATE (start)-[r:PATH]->(end) SET r += row.properties;
+            UNWIND [{start: {_id:2}, end: {_id:1}, properties:{step:0}}] AS row
+            MATCH (start:`UNIQUE IMPORT LABEL`{`UNIQUE IMPORT ID`: row.start._id})
+            MATCH (end:`UNIQUE IMPORT LABEL`{`UNIQUE IMPORT ID`: row.end._id})
+            CREATE (start)-[r:VISITED]->(end) SET r += row.properties;
+            :commit
+            :begin
+            MATCH (n:`UNIQUE IMPORT LABEL`)  WITH n LIMIT 20000 REMOVE n:`UNIQUE IMPORT LABEL`, n.`UNIQUE IMPORT ID`;
+            :commit
+        "#;
+        // The same without batching.
+        let plain = r#"
+            CREATE (:`Company`:`UNIQUE IMPORT LABEL` {`name`:"Neo4j", `swag`:350, `timePrice`:147, `UNIQUE IMPORT ID`:0});
+            CREATE (:`Company`:`UNIQUE IMPORT LABEL` {`name`:"Dovu", `swag`:129, `timePrice`:27, `UNIQUE IMPORT ID`:1});
+            MATCH (n1:`UNIQUE IMPORT LABEL`{`UNIQUE IMPORT ID`:0}), (n2:`UNIQUE IMPORT LABEL`{`UNIQUE IMPORT ID`:1}) CREATE (n1)-[r:`PATH` {`timePrice`:24}]->(n2);
+            MATCH (n1:`UNIQUE IMPORT LABEL`{`UNIQUE IMPORT ID`:1}), (n2:`UNIQUE IMPORT LABEL`{`UNIQUE IMPORT ID`:0}) CREATE (n1)-[r:`PATH` {`timePrice`:25}]->(n2);
+        "#;
+        let names = PropertyNames { path_type: Some("PATH".to_string()), ..PropertyNames::default() };
+        for script in [batched, plain] {
+            let (nodes, relationships) = cypher_to_structures(script, &names).unwrap();
+            assert_eq!(sorted_names(&nodes), ["Dovu", "Neo4j"]);
+            assert_eq!(nodes["Dovu"].value, 129);
+            assert_eq!((relationships["Neo4j"].paths[0].to.as_str(), relationships["Neo4j"].paths[0].cost), ("Dovu", 24));
+            assert_eq!((relationships["Dovu"].paths[0].to.as_str(), relationships["Dovu"].paths[0].cost), ("Neo4j", 25));
+        }
+
+        let broken = "UNWIND [{start: {}, end: {_id:1}, properties:{timePrice:24}}] AS row CREATE (start)-[r:PATH]->(end);";
+        assert!(cypher_to_structures(broken, &names).err().unwrap().contains("without a start company"));
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::Node;
+use crate::rng::Rng;
+
+/// Spread of the time it takes to collect at a company, queues vary over the day.
+#[derive(Hash, PartialEq, Eq, Clone)]
+pub enum CostDistribution {
+    Triangular { min: i64, mode: i64, max: i64 },
+    Samples(Vec<i64>), // Observed collection times, drawn from uniformly.
+}
+
+impl CostDistribution {
+    /// Reads either `{"min": .., "mode": .., "max": ..}` or an array of observed times.
+    pub fn from_json(json: &serde_json::Value) -> Option<CostDistribution> {
+        if let Some(samples) = json.as_array() {
+            let samples: Vec<i64> = samples.iter().filter_map(|s| s.as_i64()).collect();
+            if samples.is_empty() {
+                return None;
+            }
+            return Some(CostDistribution::Samples(samples));
+        }
+        let min = json.get("min")?.as_i64()?;
+        let max = json.get("max")?.as_i64()?;
+        if min > max {
+            return None;
+        }
+        let mode = json.get("mode").and_then(|m| m.as_i64()).unwrap_or((min + max) / 2);
+        Some(CostDistribution::Triangular { min, mode: mode.clamp(min, max), max })
+    }
+
+    pub fn sample(&self, rng: &mut Rng) -> i64 {
+        match self {
+            CostDistribution::Triangular { .. } => self.quantile(rng.next_f64()),
+            CostDistribution::Samples(samples) => samples[rng.below(samples.len())],
+        }
+    }
+
+    /// The cost which is not exceeded with probability `q`.
+    pub fn quantile(&self, q: f64) -> i64 {
+        let q = q.clamp(0.0, 1.0);
+        match self {
+            CostDistribution::Triangular { min, mode, max } => {
+                let (a, c, b) = (*min as f64, *mode as f64, *max as f64);
+                if b <= a {
+                    return *min;
+                }
+                let x = if q < (c - a) / (b - a) {
+                    a + (q * (b - a) * (c - a)).sqrt()
+                } else {
+                    b - ((1.0 - q) * (b - a) * (b - c)).sqrt()
+                };
+                x.round() as i64
+            }
+            CostDistribution::Samples(samples) => {
+                let mut sorted = samples.clone();
+                sorted.sort();
+                let index = ((q * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len()) - 1;
+                sorted[index]
+            }
+        }
+    }
+}
+
+/// Parses `min,mode,max` or `min,max` (mode halfway) as a triangular distribution.
+impl FromStr for CostDistribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let numbers = s.split(',').map(|n| n.trim().parse::<i64>())
+            .collect::<Result<Vec<_>, _>>().map_err(|_| format!("{} is not min,mode,max", s))?;
+        match numbers[..] {
+            [min, max] if min <= max => Ok(CostDistribution::Triangular { min, mode: (min + max) / 2, max }),
+            [min, mode, max] if min <= mode && mode <= max => Ok(CostDistribution::Triangular { min, mode, max }),
+            _ => Err(format!("{} is not min,mode,max with min <= mode <= max", s)),
+        }
+    }
+}
+
+impl Display for CostDistribution {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CostDistribution::Triangular { min, mode, max } => write!(f, "tri({}, {}, {})", min, mode, max),
+            CostDistribution::Samples(samples) => write!(f, "{} samples", samples.len()),
+        }
+    }
+}
+
+/// Copies the nodes with every uncertain cost replaced by its `q` quantile, planning on these
+/// optimizes for the chosen quantile instead of the nominal `timePrice`.
+pub fn quantile_nodes(nodes: &HashMap<String, Node>, q: f64) -> HashMap<String, Node> {
+    nodes.iter().map(|(name, node)| {
+        let mut planned = node.clone();
+        if let Some(distribution) = &node.distribution {
+            planned.cost = distribution.quantile(q);
+        }
+        (name.clone(), planned)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triangular_quantiles() {
+        let triangle: CostDistribution = "10,40,110".parse().unwrap();
+        assert_eq!(triangle.quantile(0.0), 10);
+        assert_eq!(triangle.quantile(1.0), 110);
+        // The mode is the 30% quantile, (40 - 10) / (110 - 10).
+        assert_eq!(triangle.quantile(0.3), 40);
+        assert!(triangle.quantile(0.5) > 40 && triangle.quantile(0.5) < 60);
+        assert_eq!(triangle.quantile(2.0), 110);
+        assert_eq!("5,5".parse::<CostDistribution>().unwrap().quantile(0.7), 5);
+        assert!("30,10,20".parse::<CostDistribution>().is_err());
+    }
+
+    #[test]
+    fn sample_quantiles() {
+        let samples = CostDistribution::from_json(&serde_json::json!([40, 10, 30, 20])).unwrap();
+        assert_eq!(samples.quantile(0.0), 10);
+        assert_eq!(samples.quantile(0.25), 10);
+        assert_eq!(samples.quantile(0.5), 20);
+        assert_eq!(samples.quantile(0.9), 40);
+        assert_eq!(samples.quantile(1.0), 40);
+        assert!(CostDistribution::from_json(&serde_json::json!([])).is_none());
+    }
+
+    #[test]
+    fn swapped_bounds_are_no_distribution() {
+        assert!(CostDistribution::from_json(&serde_json::json!({"min": 30, "max": 10})).is_none());
+        let clamped = CostDistribution::from_json(&serde_json::json!({"min": 10, "mode": 50, "max": 30})).unwrap();
+        assert_eq!(clamped.to_string(), "tri(10, 30, 30)");
+    }
+
+    #[test]
+    fn plans_on_the_quantile_of_uncertain_costs_only() {
+        let nodes: HashMap<String, Node> = [
+            Node::new("Queue".to_string(), 100, 30).with_distribution("10,30,90".parse().unwrap()),
+            Node::new("Fixed".to_string(), 100, 30),
+        ].into_iter().map(|n| (n.name.clone(), n)).collect();
+        let planned = quantile_nodes(&nodes, 1.0);
+        assert_eq!((planned["Queue"].cost, planned["Fixed"].cost), (90, 30));
+    }
+}
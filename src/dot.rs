@@ -0,0 +1,123 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+
+use crate::{Action, Node, Relationships, Subnets};
+
+/// Backslashes first, so the ones escaping quotes are not doubled.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", escape(name))
+}
+
+/// Node width in inches from its value per cost, bad and free nodes get the extremes.
+fn width(node: &Node) -> f64 {
+    let vpc = node.value_per_cost();
+    if vpc.is_nan() { 0.5 } else { (0.5 + vpc).clamp(0.5, 3.0) }
+}
+
+fn write_node(dot: &mut String, indent: &str, node: &Node, collected: bool) {
+    let fill = if collected { ", style=filled, fillcolor=\"#9ad29a\"" } else { "" };
+    writeln!(dot, "{}{} [label=\"{}\\nswag {} / time {}\", width={:.2}{}];",
+             indent, quote(&node.name), escape(&node.name), node.value, node.cost, width(node), fill).unwrap();
+}
+
+/// Cheap paths pull their companies closer together in the layout.
+fn weight(cost: i64) -> i64 {
+    (1000 / cost.max(1)).max(1)
+}
+
+/// Graphviz DOT of the graph with every subnet as a cluster. If a route is given its hops are
+/// drawn red and numbered by step and the companies collected at are filled.
+pub fn to_dot(nodes: &HashMap<String, Node>, relationships: &Relationships,
+              subnets: &Subnets<f64>, route: Option<&[Action]>) -> String {
+    let route = route.unwrap_or(&[]);
+    let collected: HashSet<&String> = route.iter().filter(|a| a.collected).map(|a| &a.company).collect();
+    // Step numbers of every hop, keyed by (from, to).
+    let mut steps: BTreeMap<(&str, &str), Vec<usize>> = BTreeMap::new();
+    for (i, hop) in route.windows(2).enumerate() {
+        steps.entry((&hop[0].company, &hop[1].company)).or_default().push(i + 1);
+    }
+
+    let mut dot = String::new();
+    writeln!(dot, "digraph companies {{").unwrap();
+    writeln!(dot, "    node [shape=ellipse, fixedsize=false];").unwrap();
+
+    let mut placed = HashSet::new();
+    for (i, subnet) in subnets.subnets.iter().enumerate() {
+        writeln!(dot, "    subgraph cluster_{} {{", i).unwrap();
+        writeln!(dot, "        label=\"subnet {} (value per cost {:.2})\";", i, subnet.value_per_cost()).unwrap();
+        for node in &subnet.nodes {
+            write_node(&mut dot, "        ", node, collected.contains(&node.name));
+            placed.insert(&node.name);
+        }
+        writeln!(dot, "    }}").unwrap();
+    }
+    let mut names: Vec<&String> = nodes.keys().filter(|n| !placed.contains(n)).collect();
+    names.sort();
+    for name in names {
+        write_node(&mut dot, "    ", &nodes[name], collected.contains(name));
+    }
+
+    let mut froms: Vec<&String> = relationships.keys().collect();
+    froms.sort();
+    for from in froms {
+        for path in &relationships[from].paths {
+            match steps.remove(&(from.as_str(), path.to.as_str())) {
+                Some(hops) => {
+                    let hops: Vec<String> = hops.iter().map(|s| s.to_string()).collect();
+                    writeln!(dot, "    {} -> {} [label=\"{} (#{})\", weight={}, color=red, penwidth=3];",
+                             quote(from), quote(&path.to), path.cost, hops.join(", #"), weight(path.cost)).unwrap();
+                }
+                None => writeln!(dot, "    {} -> {} [label=\"{}\", weight={}, color=gray];",
+                                 quote(from), quote(&path.to), path.cost, weight(path.cost)).unwrap(),
+            }
+        }
+    }
+    if let Some(start) = route.first() {
+        writeln!(dot, "    {} [penwidth=3, color=red];", quote(&start.company)).unwrap();
+    }
+    writeln!(dot, "}}").unwrap();
+    dot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Path, Relationship, Subnet};
+
+    #[test]
+    fn draws_subnets_paths_and_the_route() {
+        let nodes: HashMap<String, Node> = [("Neo4j", 0, 0), ("A \"Corp\"", 100, 20), ("B", 50, 10)].iter()
+            .map(|(name, value, cost)| (name.to_string(), Node::new(name.to_string(), *value, *cost)))
+            .collect();
+        let mut relationships = Relationships::new();
+        for (from, to) in [("Neo4j", "A \"Corp\""), ("A \"Corp\"", "B"), ("B", "Neo4j")] {
+            relationships.insert(from.to_string(), Relationship { paths: vec![Path { to: to.to_string(), cost: 10 }] });
+        }
+        // A and B make up a subnet, Neo4j is drawn outside of it.
+        let subnets = Subnets { subnets: vec![Subnet::new(&vec![nodes["A \"Corp\""].clone(), nodes["B"].clone()])] };
+        let route = [Action::new("Neo4j".to_string(), false), Action::new("A \"Corp\"".to_string(), true)];
+        let dot = to_dot(&nodes, &relationships, &subnets, Some(&route));
+
+        assert!(dot.starts_with("digraph companies {\n") && dot.ends_with("}\n"));
+        assert!(dot.contains("    subgraph cluster_0 {\n        label=\"subnet 0 (value per cost 5.00)\";\n        \"A"));
+        assert!(dot.contains("    }\n    \"Neo4j\" [label="));
+        assert!(dot.contains("\"A \\\"Corp\\\"\" [label=\"A \\\"Corp\\\"\\nswag 100 / time 20\", width=3.00, \
+                              style=filled, fillcolor=\"#9ad29a\"];"));
+        assert!(dot.contains("\"B\" [label=\"B\\nswag 50 / time 10\", width=3.00];"));
+        assert!(dot.contains("    \"Neo4j\" -> \"A \\\"Corp\\\"\" [label=\"10 (#1)\", weight=100, color=red, penwidth=3];"));
+        assert!(dot.contains("    \"B\" -> \"Neo4j\" [label=\"10\", weight=100, color=gray];"));
+        assert!(dot.contains("    \"Neo4j\" [penwidth=3, color=red];"));
+        assert_eq!(dot.matches(" -> ").count(), 3);
+    }
+
+    #[test]
+    fn escapes_backslashes_in_names_and_labels() {
+        let mut dot = String::new();
+        write_node(&mut dot, "", &Node::new("Booth \\\"7\"".to_string(), 10, 5), false);
+        assert_eq!(dot, "\"Booth \\\\\\\"7\\\"\" [label=\"Booth \\\\\\\"7\\\"\\nswag 10 / time 5\", width=2.50];\n");
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt::{Display, Formatter};
+
+use serde_json::{json, Value};
+
+use crate::Action;
+
+/// A path considered at a decision, with the moves after it the algorithm's score assumes.
+pub struct Candidate {
+    pub(crate) to: String,
+    pub(crate) travel: i64,
+    pub(crate) score: f64,
+    pub(crate) line: Vec<Action>,
+}
+
+/// Why a step of the route was taken and why swag was or was not collected there. The first
+/// step of a route and planned steps have no candidates.
+pub struct Decision {
+    pub(crate) step: usize,
+    pub(crate) from: String,
+    pub(crate) time_left: i64,
+    pub(crate) candidates: Vec<Candidate>,
+    pub(crate) chosen: String,
+    pub(crate) planned: bool, // Made as planned by the last search, without searching again.
+    pub(crate) wanted: bool, // Whether the algorithm asked to collect.
+    pub(crate) already_collected: bool,
+    pub(crate) ratio: f64, // Swag per time of collecting at the chosen company.
+    pub(crate) limit: Option<f64>,
+    pub(crate) collected: bool,
+}
+
+/// JSON has no infinities, which a collection in no time gives the swag per time, so these
+/// are written as "inf" and "-inf" (and NaN as "nan").
+fn number(x: f64) -> Value {
+    if x.is_finite() {
+        json!(x)
+    } else if x.is_nan() {
+        json!("nan")
+    } else if x > 0.0 {
+        json!("inf")
+    } else {
+        json!("-inf")
+    }
+}
+
+impl Decision {
+    pub fn reason(&self) -> String {
+        let limit = match self.limit {
+            Some(limit) => format!("the limit {:.2}", limit),
+            None => "what the algorithm asks for".to_string(),
+        };
+        if self.already_collected {
+            "already collected earlier".to_string()
+        } else if !self.wanted {
+            format!("{:.2} swag per time is not worth it against {}", self.ratio, limit)
+        } else if !self.collected {
+            format!("{:.2} swag per time is worth it against {}, but the rules did not allow it \
+                     (closed, out of time or ttl used up)", self.ratio, limit)
+        } else {
+            format!("{:.2} swag per time is worth it against {}", self.ratio, limit)
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "step": self.step,
+            "from": self.from,
+            "time_left": self.time_left,
+            "candidates": self.candidates.iter().map(|c| json!({
+                "to": c.to,
+                "travel": c.travel,
+                "score": number(c.score),
+                "line": c.line.iter().map(|a| json!({"company": a.company, "collected": a.collected})).collect::<Vec<_>>(),
+            })).collect::<Vec<_>>(),
+            "chosen": self.chosen,
+            "planned": self.planned,
+            "collected": self.collected,
+            "swag_per_time": number(self.ratio),
+            "collect_limit": self.limit.map(number),
+            "reason": self.reason(),
+        })
+    }
+}
+
+impl Display for Decision {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.planned {
+            writeln!(f, "Step {}: from {} to {} as planned, with {} time left", self.step, self.from,
+                     self.chosen, self.time_left)?;
+        } else if self.candidates.is_empty() {
+            writeln!(f, "Step {}: start at {} with {} time left", self.step, self.chosen, self.time_left)?;
+        } else {
+            writeln!(f, "Step {}: from {} with {} time left", self.step, self.from, self.time_left)?;
+        }
+        for candidate in &self.candidates {
+            let marker = if candidate.to == self.chosen { ">" } else { " " };
+            write!(f, "  {} {} (travel {}) scores {}", marker, candidate.to, candidate.travel, candidate.score)?;
+            if !candidate.line.is_empty() {
+                let line: Vec<String> = candidate.line.iter().map(|a| a.to_string()).collect();
+                write!(f, ", then {}", line.join(" "))?;
+            }
+            writeln!(f)?;
+        }
+        let verb = if self.collected { "Collected" } else { "Did not collect" };
+        writeln!(f, "  {} at {}: {}", verb, self.chosen, self.reason())
+    }
+}
+
+pub fn to_json(decisions: &[Decision]) -> Value {
+    Value::from(decisions.iter().map(|d| d.to_json()).collect::<Vec<_>>())
+}
+
+pub fn to_text(decisions: &[Decision]) -> String {
+    decisions.iter().map(|d| d.to_string()).collect::<Vec<_>>().join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn infinite_numbers_are_written_as_strings() {
+        let decision = Decision {
+            step: 1, from: "Neo4j".to_string(), time_left: 100,
+            candidates: vec![Candidate { to: "Free".to_string(), travel: 10, score: f64::NEG_INFINITY, line: vec![] }],
+            chosen: "Free".to_string(), planned: false, wanted: true, already_collected: false,
+            ratio: f64::INFINITY, limit: Some(0.5), collected: true,
+        };
+        let json = decision.to_json();
+        assert_eq!(json["swag_per_time"], "inf");
+        assert_eq!(json["candidates"][0]["score"], "-inf");
+        assert_eq!(json["collect_limit"], 0.5);
+        assert_eq!(number(f64::NAN), "nan");
+    }
+}
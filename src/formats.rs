@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use std::fs;
+use std::str::FromStr;
+
+use serde_json::{Map, Value};
+
+use crate::{apoc, csv, cypher, distribution, graphml, neo4j_json_to_structures, structures_to_neo4j_json};
+use crate::{Node, Path, Relationship, Relationships, TimeWindow};
+
+/// Which graph properties hold what the solvers need.
+#[derive(Clone)]
+pub struct PropertyNames {
+    pub(crate) name: String,
+    pub(crate) value: String,
+    pub(crate) cost: String,
+    pub(crate) path_cost: String,
+    pub(crate) path_type: Option<String>, // Only relationships of this type are paths, all if None.
+}
+
+impl Default for PropertyNames {
+    fn default() -> Self {
+        PropertyNames {
+            name: "name".to_string(),
+            value: "swag".to_string(),
+            cost: "timePrice".to_string(),
+            path_cost: "timePrice".to_string(),
+            path_type: None,
+        }
+    }
+}
+
+pub enum Format {
+    Json, // The { "nodes": {...}, "relationships": {...} } shape of data.json.
+    Apoc, // apoc.export.json, JSON lines or an array of nodes and relationships.
+    Cypher, // A script of CREATE (and MATCH/MERGE) statements.
+    Csv, // A nodes file `id,name,swag,timePrice` and an edges file `from,to,timePrice`.
+    GraphMl,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Format::Json),
+            "apoc" => Ok(Format::Apoc),
+            "cypher" => Ok(Format::Cypher),
+            "csv" => Ok(Format::Csv),
+            "graphml" => Ok(Format::GraphMl),
+            other => Err(format!("Unknown format {}, expected json, apoc, cypher, csv or graphml", other)),
+        }
+    }
+}
+
+impl Format {
+    /// Guesses the format from the file extension, data.json style if unsure.
+    pub fn from_path(path: &str) -> Format {
+        if path.ends_with(".cypher") || path.ends_with(".cql") {
+            Format::Cypher
+        } else if path.ends_with(".jsonl") || path.ends_with(".ndjson") {
+            Format::Apoc
+        } else if path.ends_with(".csv") {
+            Format::Csv
+        } else if path.ends_with(".graphml") {
+            Format::GraphMl
+        } else {
+            Format::Json
+        }
+    }
+}
+
+fn read(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))
+}
+
+/// The edges file next to a nodes CSV, `nodes.csv` pairs with `nodes_edges.csv`.
+pub fn edges_path(nodes_path: &str) -> String {
+    format!("{}_edges.csv", nodes_path.strip_suffix(".csv").unwrap_or(nodes_path))
+}
+
+/// Reads a graph, `edges` is the edges file of the CSV format.
+pub fn load(path: &str, edges: Option<&str>, format: &Format, names: &PropertyNames) -> Result<(HashMap<String, Node>, Relationships), String> {
+    let text = read(path)?;
+    match format {
+        Format::Json => {
+            let json: Value = serde_json::from_str(&text).map_err(|e| format!("Could not parse {}: {}", path, e))?;
+            Ok(neo4j_json_to_structures(&json))
+        }
+        Format::Apoc => apoc::apoc_json_to_structures(&text, names),
+        Format::Cypher => cypher::cypher_to_structures(&text, names),
+        Format::Csv => csv::csv_to_structures(&text, &read(&edges.map_or_else(|| edges_path(path), |e| e.to_string()))?, names),
+        Format::GraphMl => graphml::graphml_to_structures(&text, names),
+    }
+}
+
+/// Writes the graph in the format, the CSV format also writes the edges file.
+pub fn save(path: &str, edges: Option<&str>, format: &Format, names: &PropertyNames,
+            nodes: &HashMap<String, Node>, relationships: &Relationships) -> Result<(), String> {
+    let write = |path: &str, contents: String| fs::write(path, contents).map_err(|e| format!("Could not write {}: {}", path, e));
+    match format {
+        Format::Json => write(path, serde_json::to_string_pretty(&structures_to_neo4j_json(nodes, relationships)).unwrap()),
+        Format::Apoc => write(path, apoc::structures_to_apoc_json(nodes, relationships, names)),
+        Format::Cypher => write(path, cypher::structures_to_cypher(nodes, relationships, names)),
+        Format::Csv => {
+            let (nodes_csv, edges_csv) = csv::structures_to_csv(nodes, relationships, names);
+            write(path, nodes_csv)?;
+            write(&edges.map_or_else(|| edges_path(path), |e| e.to_string()), edges_csv)
+        }
+        Format::GraphMl => write(path, graphml::structures_to_graphml(nodes, relationships, names)),
+    }
+}
+
+/// Company names in a stable order for writing.
+pub fn sorted_names(nodes: &HashMap<String, Node>) -> Vec<&String> {
+    let mut names: Vec<&String> = nodes.keys().collect();
+    names.sort();
+    names
+}
+
+/// The time window, cost distribution and category of a company as properties.
+pub fn optional_properties(node: &Node) -> Map<String, Value> {
+    let mut properties = Map::new();
+    if let Some(window) = &node.window {
+        properties.insert("earliest".to_string(), Value::from(window.earliest));
+        if window.latest != i64::MAX {
+            properties.insert("latest".to_string(), Value::from(window.latest));
+        }
+    }
+    match &node.distribution {
+        Some(distribution::CostDistribution::Triangular { min, mode, max }) => {
+            properties.insert("timePriceDistribution".to_string(), serde_json::json!({"min": min, "mode": mode, "max": max}));
+        }
+        Some(distribution::CostDistribution::Samples(samples)) => {
+            properties.insert("timePriceDistribution".to_string(), Value::from(samples.clone()));
+        }
+        None => {}
+    }
+    if let Some(category) = &node.category {
+        properties.insert("category".to_string(), Value::from(category.clone()));
+    }
+    properties
+}
+
+/// Reads the optional time window, cost distribution and category of a company.
+pub fn with_optional_properties(mut node: Node, properties: &Map<String, Value>) -> Node {
+    // Booths without "earliest"/"latest" are open the whole event.
+    let earliest = properties.get("earliest").and_then(|e| e.as_i64());
+    let latest = properties.get("latest").and_then(|l| l.as_i64());
+    if earliest.is_some() || latest.is_some() {
+        node = node.with_window(TimeWindow::new(earliest.unwrap_or(0), latest.unwrap_or(i64::MAX)));
+    }
+    // CSV and GraphML hold the distribution as JSON text.
+    let distribution = match properties.get("timePriceDistribution") {
+        Some(Value::String(text)) => serde_json::from_str(text).ok(),
+        other => other.cloned(),
+    };
+    if let Some(distribution) = distribution.as_ref().and_then(distribution::CostDistribution::from_json) {
+        node = node.with_distribution(distribution);
+    }
+    node.category = properties.get("category").and_then(|c| c.as_str()).map(|c| c.to_string());
+    node
+}
+
+/// A company as read from a property map, None if it lacks swag or time price and so is not
+/// a company at all.
+pub fn node_from_properties(id: &str, properties: &Map<String, Value>, names: &PropertyNames) -> Option<Node> {
+    let name = match properties.get(&names.name) {
+        Some(Value::String(name)) => name.clone(),
+        Some(Value::Number(name)) => name.to_string(),
+        _ => id.to_string(),
+    };
+    let value = properties.get(&names.value)?.as_i64()?;
+    let cost = properties.get(&names.cost)?.as_i64()?;
+    Some(with_optional_properties(Node::new(name, value, cost), properties))
+}
+
+/// Builds the solver structures from nodes keyed by an import id and edges between those ids.
+/// Every company gets a relationship entry, possibly without paths.
+pub fn build(nodes: Vec<(String, Node)>, edges: Vec<(String, String, i64)>) -> Result<(HashMap<String, Node>, Relationships), String> {
+    let mut names: HashMap<String, String> = HashMap::new();
+    let mut resulting_nodes = HashMap::new();
+    let mut relationships = Relationships::new();
+    for (id, node) in nodes {
+        if resulting_nodes.contains_key(&node.name) {
+            return Err(format!("Company {} appears twice.", node.name));
+        }
+        names.insert(id, node.name.clone());
+        relationships.insert(node.name.clone(), Relationship{ paths: vec![] });
+        resulting_nodes.insert(node.name.clone(), node);
+    }
+    for (from, to, cost) in edges {
+        let from = names.get(&from).ok_or(format!("Path from unknown company {}.", from))?;
+        let to = names.get(&to).ok_or(format!("Path to unknown company {}.", to))?;
+        relationships.get_mut(from).unwrap().paths.push(Path{ to: to.clone(), cost });
+    }
+    Ok((resulting_nodes, relationships))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distribution::CostDistribution;
+
+    fn written_and_read(format: &Format, nodes: &HashMap<String, Node>, relationships: &Relationships,
+                        names: &PropertyNames) -> (HashMap<String, Node>, Relationships) {
+        match format {
+            Format::Json => {
+                let text = serde_json::to_string(&structures_to_neo4j_json(nodes, relationships)).unwrap();
+                neo4j_json_to_structures(&serde_json::from_str(&text).unwrap())
+            }
+            Format::Apoc => apoc::apoc_json_to_structures(&apoc::structures_to_apoc_json(nodes, relationships, names), names).unwrap(),
+            Format::Cypher => cypher::cypher_to_structures(&cypher::structures_to_cypher(nodes, relationships, names), names).unwrap(),
+            Format::Csv => {
+                let (nodes_csv, edges_csv) = csv::structures_to_csv(nodes, relationships, names);
+                csv::csv_to_structures(&nodes_csv, &edges_csv, names).unwrap()
+            }
+            Format::GraphMl => graphml::graphml_to_structures(&graphml::structures_to_graphml(nodes, relationships, names), names).unwrap(),
+        }
+    }
+
+    #[test]
+    fn every_format_round_trips() {
+        let mut late = Node::new("Big, \"Corp\" & <Sons>".to_string(), 100, 10)
+            .with_window(TimeWindow::new(60, 300))
+            .with_distribution(CostDistribution::Triangular { min: 5, mode: 10, max: 30 });
+        late.category = Some("shirt".to_string());
+        let nodes: HashMap<String, Node> = [
+            Node::new("Neo4j".to_string(), 0, 0),
+            Node::new("007".to_string(), 50, 5).with_distribution(CostDistribution::Samples(vec![3, 5, 9])),
+            late,
+        ].into_iter().map(|n| (n.name.clone(), n)).collect();
+        let mut relationships = Relationships::new();
+        for (from, to, cost) in [("Neo4j", "007", 20), ("007", "Big, \"Corp\" & <Sons>", 15), ("Big, \"Corp\" & <Sons>", "Neo4j", 30)] {
+            relationships.entry(from.to_string()).or_insert(Relationship { paths: vec![] })
+                .paths.push(Path { to: to.to_string(), cost });
+        }
+        let names = PropertyNames::default();
+        for format in ["json", "apoc", "cypher", "csv", "graphml"] {
+            let (again, again_relationships) = written_and_read(&format.parse().unwrap(), &nodes, &relationships, &names);
+            assert_eq!(sorted_names(&again), sorted_names(&nodes), "{}", format);
+            for (name, node) in &nodes {
+                let read = &again[name];
+                assert_eq!((read.value, read.cost), (node.value, node.cost), "{} {}", format, name);
+                assert_eq!(optional_properties(read), optional_properties(node), "{} {}", format, name);
+                let paths = |r: &Relationships| r[name].paths.iter().map(|p| (p.to.clone(), p.cost)).collect::<Vec<_>>();
+                assert_eq!(paths(&again_relationships), paths(&relationships), "{} {}", format, name);
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+
+use crate::{Node, Path, Relationship, Relationships};
+use crate::distribution::CostDistribution;
+use crate::rng::Rng;
+
+/// Shape of a random instance. The defaults resemble `data.json`.
+pub struct GeneratorConfig {
+    pub nodes: usize,
+    // Companies are spread round robin over this many clusters, each becoming a category.
+    pub clusters: usize,
+    // Probability of a path between two companies of the same cluster.
+    pub density: f64,
+    // Probability of a path between two companies of different clusters.
+    pub inter_density: f64,
+    pub swag: CostDistribution,
+    pub time_price: CostDistribution,
+    pub path_time: CostDistribution,
+    // Share of companies whose swag is negated.
+    pub negative: f64,
+    // Whether every path has a way back taking the same time.
+    pub symmetric: bool,
+    pub start: String,
+    pub seed: u64,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        GeneratorConfig {
+            nodes: 60,
+            clusters: 6,
+            density: 0.1,
+            inter_density: 0.005,
+            swag: CostDistribution::Triangular { min: 0, mode: 130, max: 400 },
+            time_price: CostDistribution::Triangular { min: 2, mode: 110, max: 300 },
+            path_time: CostDistribution::Triangular { min: 5, mode: 40, max: 175 },
+            negative: 0.02,
+            symmetric: true,
+            start: "Neo4j".to_string(),
+            seed: 42,
+        }
+    }
+}
+
+/// A random instance, the same config always gives the same graph. The first company is named
+/// `start` and the companies of a cluster are chained both ways and the clusters joined in a
+/// ring, so every company can be reached from every other.
+pub fn generate(config: &GeneratorConfig) -> (HashMap<String, Node>, Relationships) {
+    let mut rng = Rng::new(config.seed);
+    let clusters = config.clusters.clamp(1, config.nodes.max(1));
+    let names: Vec<String> = (0..config.nodes)
+        .map(|i| if i == 0 { config.start.clone() } else { format!("Company{}", i) }).collect();
+    let cluster_of = |i: usize| i % clusters;
+
+    let mut nodes = HashMap::new();
+    for (i, name) in names.iter().enumerate() {
+        let mut value = config.swag.sample(&mut rng);
+        if rng.next_f64() < config.negative {
+            value = -value.max(1);
+        }
+        let mut node = Node::new(name.clone(), value, config.time_price.sample(&mut rng).max(0));
+        node.category = Some(format!("cluster{}", cluster_of(i)));
+        nodes.insert(name.clone(), node);
+    }
+
+    let mut edges: Vec<Vec<Option<i64>>> = vec![vec![None; names.len()]; names.len()];
+    let mut connect = |rng: &mut Rng, from: usize, to: usize, both: bool| {
+        let time = config.path_time.sample(rng).max(0);
+        if edges[from][to].is_none() {
+            edges[from][to] = Some(time);
+        }
+        if both && edges[to][from].is_none() {
+            edges[to][from] = Some(time);
+        }
+    };
+    for i in clusters..names.len() {
+        connect(&mut rng, i - clusters, i, true);
+    }
+    if clusters > 1 {
+        for c in 0..clusters {
+            connect(&mut rng, c, (c + 1) % clusters, true);
+        }
+    }
+    for from in 0..names.len() {
+        for to in 0..names.len() {
+            let density = if cluster_of(from) == cluster_of(to) { config.density } else { config.inter_density };
+            if from != to && rng.next_f64() < density {
+                connect(&mut rng, from, to, config.symmetric);
+            }
+        }
+    }
+
+    let relationships = names.iter().enumerate().map(|(from, name)| {
+        let paths = edges[from].iter().enumerate()
+            .filter_map(|(to, time)| time.map(|cost| Path{ to: names[to].clone(), cost })).collect();
+        (name.clone(), Relationship{ paths })
+    }).collect();
+    (nodes, relationships)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphs::travel_times;
+
+    #[test]
+    fn same_seed_same_connected_graph() {
+        let config = GeneratorConfig { nodes: 40, negative: 0.5, ..GeneratorConfig::default() };
+        let (nodes, relationships) = generate(&config);
+        let (again, _) = generate(&config);
+
+        assert_eq!(nodes.len(), 40);
+        assert!(nodes.iter().all(|(name, node)| again[name].value == node.value && again[name].cost == node.cost));
+        assert!(nodes.values().any(|n| n.value < 0));
+        for name in nodes.keys() {
+            assert_eq!(travel_times(&relationships, name).len(), 40);
+            assert!(relationships[name].paths.iter().all(|p| &p.to != name));
+        }
+        let (other, _) = generate(&GeneratorConfig { seed: 7, ..config });
+        assert!(nodes.iter().any(|(name, node)| other[name].value != node.value));
+    }
+}
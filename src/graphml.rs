@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use serde_json::{Map, Value};
+
+use crate::{Node, Relationships};
+use crate::formats::{build, node_from_properties, optional_properties, sorted_names, PropertyNames};
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"")
+        .replace("&apos;", "'").replace("&amp;", "&")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Where the tag ends, the first `>` outside quoted attribute values, which may hold one.
+fn tag_end(tag: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in tag.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return Some(i),
+            (Some(q), _) if q == c => quote = None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// A start tag, end tag or the text between them.
+enum Token {
+    Start { name: String, attributes: HashMap<String, String>, empty: bool },
+    End(String),
+    Text(String),
+}
+
+fn tokenize(xml: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut rest = xml;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("<!--") {
+            let end = after.find("-->").ok_or("Unterminated comment.")?;
+            rest = &after[end + 3..];
+        } else if let Some(after) = rest.strip_prefix("<![CDATA[") {
+            let end = after.find("]]>").ok_or("Unterminated CDATA.")?;
+            tokens.push(Token::Text(after[..end].to_string()));
+            rest = &after[end + 3..];
+        } else if rest.starts_with("<?") || rest.starts_with("<!") {
+            let end = rest.find('>').ok_or("Unterminated declaration.")?;
+            rest = &rest[end + 1..];
+        } else if let Some(after) = rest.strip_prefix('<') {
+            let end = tag_end(after).ok_or("Unterminated tag.")?;
+            let tag = &after[..end];
+            rest = &after[end + 1..];
+            if let Some(name) = tag.strip_prefix('/') {
+                tokens.push(Token::End(name.trim().to_string()));
+                continue;
+            }
+            let empty = tag.ends_with('/');
+            let tag = tag.trim_end_matches('/');
+            let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+            let mut attributes = HashMap::new();
+            let mut attrs = &tag[name_end..];
+            while let Some(eq) = attrs.find('=') {
+                let key = attrs[..eq].trim().to_string();
+                let value = attrs[eq + 1..].trim_start();
+                let quote = value.chars().next().ok_or("Attribute without value.")?;
+                if quote != '"' && quote != '\'' {
+                    return Err("Attribute without quotes.".to_string());
+                }
+                let close = value[1..].find(quote).ok_or("Unterminated attribute.")?;
+                attributes.insert(key, unescape(&value[1..close + 1]));
+                attrs = &value[close + 2..];
+            }
+            tokens.push(Token::Start { name: tag[..name_end].to_string(), attributes, empty });
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            tokens.push(Token::Text(unescape(&rest[..end])));
+            rest = &rest[end..];
+        }
+    }
+    Ok(tokens)
+}
+
+fn typed(text: &str, kind: Option<&String>) -> Value {
+    let text = text.trim();
+    match kind.map(|k| k.as_str()) {
+        Some("int" | "long") => text.parse::<i64>().map(Value::from).unwrap_or(Value::Null),
+        Some("double" | "float") => text.parse::<f64>().map(|f| Value::from(f.round() as i64)).unwrap_or(Value::Null),
+        Some("boolean") => Value::Bool(text == "true"),
+        _ => Value::String(text.to_string()),
+    }
+}
+
+/// A `<node>` or `<edge>` being read.
+struct Element {
+    edge: bool,
+    properties: Map<String, Value>,
+    attributes: HashMap<String, String>,
+    label: Option<String>, // yEd keeps the visible name in a NodeLabel.
+}
+
+#[derive(Default)]
+struct Reader {
+    keys: HashMap<String, (String, Option<String>)>, // Key id to (property name, type).
+    nodes: Vec<(String, Node)>,
+    edges: Vec<(String, String, i64)>,
+    undirected: bool,
+}
+
+impl Reader {
+    fn finish(&mut self, element: Element, names: &PropertyNames) {
+        let Element { edge, mut properties, attributes, label } = element;
+        if !edge {
+            if let Some(id) = attributes.get("id") {
+                if let Some(label) = label.filter(|l| !l.is_empty()) {
+                    properties.entry(names.name.clone()).or_insert(Value::String(label));
+                }
+                if let Some(node) = node_from_properties(id, &properties, names) {
+                    self.nodes.push((id.clone(), node));
+                }
+            }
+        } else if let (Some(from), Some(to), Some(cost)) = (attributes.get("source"), attributes.get("target"),
+                                                            properties.get(&names.path_cost).and_then(|c| c.as_i64())) {
+            self.edges.push((from.clone(), to.clone(), cost));
+            let directed = attributes.get("directed").map(|d| d == "true").unwrap_or(!self.undirected);
+            if !directed {
+                self.edges.push((to.clone(), from.clone(), cost));
+            }
+        }
+    }
+}
+
+/// Reads GraphML, as written by yEd and most graph tools. Node and edge properties are the
+/// `<data>` of declared `<key>`s, a yEd node label is used as the name if there is no name
+/// property. Edges of undirected graphs are paths both ways.
+pub fn graphml_to_structures(xml: &str, names: &PropertyNames) -> Result<(HashMap<String, Node>, Relationships), String> {
+    let mut reader = Reader::default();
+    let mut element: Option<Element> = None;
+    let mut data_key: Option<String> = None;
+    let mut text = String::new();
+    for token in tokenize(xml)? {
+        match token {
+            Token::Start { name, attributes, empty } => match name.as_str() {
+                "key" => {
+                    if let (Some(id), Some(attr)) = (attributes.get("id"), attributes.get("attr.name")) {
+                        reader.keys.insert(id.clone(), (attr.clone(), attributes.get("attr.type").cloned()));
+                    }
+                }
+                "graph" => reader.undirected = attributes.get("edgedefault").map(|d| d.as_str()) == Some("undirected"),
+                "node" | "edge" => {
+                    let started = Element { edge: name == "edge", properties: Map::new(), attributes, label: None };
+                    if empty {
+                        reader.finish(started, names);
+                    } else {
+                        element = Some(started);
+                    }
+                }
+                "data" => {
+                    data_key = attributes.get("key").cloned();
+                    text.clear();
+                }
+                "y:NodeLabel" => text.clear(),
+                _ => {}
+            },
+            Token::Text(t) => text.push_str(&t),
+            Token::End(name) => match name.as_str() {
+                "data" => {
+                    if let (Some(key), Some(element)) = (data_key.take(), element.as_mut()) {
+                        if let Some((property, kind)) = reader.keys.get(&key) {
+                            if !text.trim().is_empty() {
+                                element.properties.insert(property.clone(), typed(&text, kind.as_ref()));
+                            }
+                        }
+                    }
+                }
+                "y:NodeLabel" => {
+                    if let Some(element) = element.as_mut() {
+                        element.label = Some(text.trim().to_string());
+                    }
+                }
+                "node" | "edge" => {
+                    if let Some(finished) = element.take() {
+                        reader.finish(finished, names);
+                    }
+                }
+                _ => {}
+            },
+        }
+    }
+    build(reader.nodes, reader.edges)
+}
+
+/// A directed GraphML graph with the companies as nodes and the paths as edges.
+pub fn structures_to_graphml(nodes: &HashMap<String, Node>, relationships: &Relationships, names: &PropertyNames) -> String {
+    let mut xml = String::new();
+    writeln!(xml, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>").unwrap();
+    writeln!(xml, "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">").unwrap();
+    let node_keys = [(&names.name, "string"), (&names.value, "int"), (&names.cost, "int")];
+    let extra_keys = [("earliest", "int"), ("latest", "int"), ("category", "string"), ("timePriceDistribution", "string")];
+    for (i, (name, kind)) in node_keys.iter().enumerate() {
+        writeln!(xml, "  <key id=\"n{}\" for=\"node\" attr.name=\"{}\" attr.type=\"{}\"/>", i, escape(name), kind).unwrap();
+    }
+    for (i, (name, kind)) in extra_keys.iter().enumerate() {
+        writeln!(xml, "  <key id=\"x{}\" for=\"node\" attr.name=\"{}\" attr.type=\"{}\"/>", i, name, kind).unwrap();
+    }
+    writeln!(xml, "  <key id=\"e0\" for=\"edge\" attr.name=\"{}\" attr.type=\"int\"/>", escape(&names.path_cost)).unwrap();
+    writeln!(xml, "  <graph id=\"companies\" edgedefault=\"directed\">").unwrap();
+    let names_sorted = sorted_names(nodes);
+    for name in &names_sorted {
+        let node = &nodes[*name];
+        writeln!(xml, "    <node id=\"{}\">", escape(name)).unwrap();
+        writeln!(xml, "      <data key=\"n0\">{}</data>", escape(name)).unwrap();
+        writeln!(xml, "      <data key=\"n1\">{}</data>", node.value).unwrap();
+        writeln!(xml, "      <data key=\"n2\">{}</data>", node.cost).unwrap();
+        let extra = optional_properties(node);
+        for (i, (key, _)) in extra_keys.iter().enumerate() {
+            match extra.get(*key) {
+                Some(Value::String(s)) => writeln!(xml, "      <data key=\"x{}\">{}</data>", i, escape(s)).unwrap(),
+                Some(v) => writeln!(xml, "      <data key=\"x{}\">{}</data>", i, escape(&v.to_string())).unwrap(),
+                None => {}
+            }
+        }
+        writeln!(xml, "    </node>").unwrap();
+    }
+    for name in &names_sorted {
+        for path in relationships.get(*name).iter().flat_map(|rel| &rel.paths) {
+            writeln!(xml, "    <edge source=\"{}\" target=\"{}\"><data key=\"e0\">{}</data></edge>",
+                     escape(name), escape(&path.to), path.cost).unwrap();
+        }
+    }
+    writeln!(xml, "  </graph>").unwrap();
+    writeln!(xml, "</graphml>").unwrap();
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_yed_labels_and_undirected_edges() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns" xmlns:y="http://www.yworks.com/xml/graphml">
+  <key for="node" id="d0" attr.name="swag" attr.type="int"/>
+  <key for="node" id="d1" attr.name="timePrice" attr.type="double"/>
+  <key for="node" id="d2" yfiles.type="nodegraphics"/>
+  <key for="edge" id="d3" attr.name="timePrice" attr.type="int"/>
+  <graph edgedefault="undirected" id="G">
+    <node id="n0"><data key="d0">350</data><data key="d1">147.0</data>
+      <data key="d2"><y:ShapeNode><y:NodeLabel alignment="center">Neo4j</y:NodeLabel></y:ShapeNode></data></node>
+    <node id="n1"><data key="d0">62</data><data key="d1">68</data>
+      <data key="d2"><y:ShapeNode><y:NodeLabel>Wagoner &amp; Co</y:NodeLabel></y:ShapeNode></data></node>
+    <edge id="e0" source="n0" target="n1"><data key="d3">22</data></edge>
+  </graph>
+</graphml>"#;
+        let (nodes, relationships) = graphml_to_structures(xml, &PropertyNames::default()).unwrap();
+        assert_eq!(nodes["Neo4j"].cost, 147);
+        assert_eq!(relationships["Neo4j"].paths[0].to, "Wagoner & Co");
+        assert_eq!(relationships["Wagoner & Co"].paths[0].cost, 22);
+    }
+
+    #[test]
+    fn unquoted_attributes_are_an_error() {
+        let xml = "<graphml><graph><node id=é/></graph></graphml>";
+        assert_eq!(graphml_to_structures(xml, &PropertyNames::default()).err(), Some("Attribute without quotes.".to_string()));
+    }
+
+    #[test]
+    fn tags_end_outside_quoted_attributes() {
+        let xml = r#"<graphml><key id="d0" attr.name="timePrice" attr.type="int"/>
+<key for="node" id="d1" attr.name="swag" attr.type="int"/><graph>
+  <node id="a>b"><data key="d1">5</data><data key="d0">1</data></node>
+  <node id='c"d>'><data key="d1">5</data><data key="d0">1</data></node>
+  <edge source="a>b" target='c"d>'><data key="d0">7</data></edge>
+</graph></graphml>"#;
+        let (_, relationships) = graphml_to_structures(xml, &PropertyNames::default()).unwrap();
+        assert_eq!(relationships["a>b"].paths[0].to, "c\"d>");
+        assert_eq!(tag_end(r#"node id="a>b">"#), Some(13));
+        assert_eq!(tag_end(r#"node id="a>b"#), None);
+    }
+}
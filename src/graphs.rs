@@ -1,7 +1,10 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
 use std::fmt::{Display, Formatter};
-use crate::Path;
+use crate::{Node, Path};
+use crate::node::NodeKind;
 
+#[derive(Clone)]
 pub struct Relationship {
     pub paths: Vec<Path>
 }
@@ -16,4 +19,115 @@ impl Display for Relationship {
     }
 }
 
-pub type Relationships = HashMap<String, Relationship>;
\ No newline at end of file
+pub type Relationships = HashMap<String, Relationship>;
+
+/// Things in a loaded graph which the solvers treat specially or which look like data errors.
+pub fn validate(nodes: &HashMap<String, Node>, relationships: &Relationships) -> Vec<String> {
+    let mut warnings = vec![];
+    let mut names: Vec<&String> = nodes.keys().collect();
+    names.sort();
+    for name in names {
+        let node = &nodes[name];
+        if node.cost < 0 {
+            warnings.push(format!("{} has negative timePrice {}.", name, node.cost));
+        }
+        match node.kind() {
+            NodeKind::PassThrough if node.value < 0 =>
+                warnings.push(format!("{} has negative swag {}, it is never collected, only passed through.", name, node.value)),
+            NodeKind::PassThrough =>
+                warnings.push(format!("{} has no swag, it is only passed through.", name)),
+            NodeKind::Free =>
+                warnings.push(format!("{} has timePrice 0, its swag is always collected.", name)),
+            NodeKind::Collectible => {}
+        }
+        for path in relationships.get(name).iter().flat_map(|rel| &rel.paths) {
+            if path.cost < 0 {
+                warnings.push(format!("Path from {} {} has negative timePrice.", name, path));
+            }
+        }
+    }
+    warnings
+}
+
+/// Shortest travel time from `from` to every company reachable from it (Dijkstra over path
+/// costs, collection times not included). Negative path costs are counted as 0.
+pub fn travel_times(relationships: &Relationships, from: &str) -> HashMap<String, i64> {
+    shortest_paths(relationships, from).into_iter().map(|(company, (time, _))| (company, time)).collect()
+}
+
+/// Like `travel_times`, with the company each shortest path comes from, None for `from` itself.
+pub fn shortest_paths(relationships: &Relationships, from: &str) -> HashMap<String, (i64, Option<String>)> {
+    shortest_paths_through(relationships, from, |_| true)
+}
+
+/// Like `shortest_paths`, only entering the companies `enter` allows.
+pub fn shortest_paths_through(relationships: &Relationships, from: &str,
+                              enter: impl Fn(&str) -> bool) -> HashMap<String, (i64, Option<String>)> {
+    let mut settled: HashMap<String, (i64, Option<String>)> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+    queue.push(Reverse((0, from.to_string(), None)));
+    while let Some(Reverse((time, company, previous))) = queue.pop() {
+        if settled.contains_key(&company) {
+            continue;
+        }
+        for path in relationships.get(&company).iter().flat_map(|rel| &rel.paths) {
+            if !settled.contains_key(&path.to) && enter(&path.to) {
+                queue.push(Reverse((time + path.cost.max(0), path.to.clone(), Some(company.clone()))));
+            }
+        }
+        settled.insert(company, (time, previous));
+    }
+    settled
+}
+
+/// The companies along the shortest path to `to`, without the start, from `shortest_paths`.
+pub fn way_to(shortest: &HashMap<String, (i64, Option<String>)>, to: &str) -> Option<Vec<String>> {
+    let mut way = vec![];
+    let mut company = to;
+    while let Some(previous) = &shortest.get(company)?.1 {
+        way.push(company.to_string());
+        company = previous;
+    }
+    way.reverse();
+    Some(way)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warns_about_negative_and_zero_cost_nodes() {
+        let nodes: HashMap<String, Node> = vec![Node::new("BadCorp".to_string(), -1, 95),
+                                                Node::new("Free".to_string(), 10, 0),
+                                                Node::new("Neo4j".to_string(), 350, 147)]
+            .into_iter().map(|n| (n.name.clone(), n)).collect();
+        let mut relationships = Relationships::new();
+        relationships.insert("Neo4j".to_string(), Relationship{ paths: vec![Path{ to: "Free".to_string(), cost: -3 }] });
+
+        let warnings = validate(&nodes, &relationships);
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings[0].starts_with("BadCorp has negative swag"));
+        assert!(warnings[1].starts_with("Free has timePrice 0"));
+        assert!(warnings[2].contains("negative timePrice"));
+    }
+
+    #[test]
+    fn shortest_ways_around_avoided_companies() {
+        let path = |to: &str, cost| Path{ to: to.to_string(), cost };
+        let mut relationships = Relationships::new();
+        relationships.insert("A".to_string(), Relationship{ paths: vec![path("B", 1), path("C", 5)] });
+        relationships.insert("B".to_string(), Relationship{ paths: vec![path("D", 1)] });
+        relationships.insert("C".to_string(), Relationship{ paths: vec![path("D", 1)] });
+
+        let shortest = shortest_paths(&relationships, "A");
+        assert_eq!(shortest["D"].0, 2);
+        assert_eq!(way_to(&shortest, "D").unwrap(), ["B", "D"]);
+        assert_eq!(way_to(&shortest, "A").unwrap(), Vec::<String>::new());
+
+        let around = shortest_paths_through(&relationships, "A", |c| c != "B");
+        assert_eq!(around["D"].0, 6);
+        assert_eq!(way_to(&around, "D").unwrap(), ["C", "D"]);
+        assert!(way_to(&around, "B").is_none());
+    }
+}
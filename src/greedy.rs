@@ -0,0 +1,221 @@
+use std::cell::{Cell, RefCell};
+
+use crate::{CollectionAlgorithm, Maximizer, Path};
+use std::collections::HashMap;
+
+use crate::graphs::{shortest_paths, shortest_paths_through, way_to};
+use crate::node::{self, NodeKind};
+use crate::route::find_path;
+use crate::rules::Revisits;
+
+type Shortest = HashMap<String, (i64, Option<String>)>;
+
+/// The company headed for and the first hop of the shortest way there.
+#[derive(Clone)]
+struct Target {
+    company: String,
+    next: String,
+    hop: i64,
+    travel: i64,
+    score: f64, // What appraise made of it.
+}
+
+/// Heads for the uncollected company with the most swag per time spent getting there and
+/// collecting, along the shortest way, and sticks with it until it is collected or out of
+/// reach. Companies on the way are collected too if worth `collect_limit` and the target still
+/// fits in the time afterwards. When revisits use up the `ttl`, ways through companies not
+/// visited yet are preferred, when they are free the shortest way is always taken.
+pub struct ShortestPathGreedy {
+    pub(crate) collect_limit: f64,
+    target: RefCell<Option<Target>>,
+    planned_at: RefCell<Option<(String, i64)>>, // Where and when the target was last checked.
+    expanded: Cell<u64>,
+}
+
+impl ShortestPathGreedy {
+    pub fn new(collect_limit: f64) -> ShortestPathGreedy {
+        ShortestPathGreedy { collect_limit, target: RefCell::new(None), planned_at: RefCell::new(None), expanded: Cell::new(0) }
+    }
+
+    /// Swag per time of going the shortest way to the company and collecting there, None if that
+    /// can not be done any more.
+    fn appraise(&self, company: &str, shortest: &Shortest, maximiser: &Maximizer) -> Option<f64> {
+        let state = &maximiser.state;
+        let node = maximiser.nodes.get(company)?;
+        if company == state.current_company || state.has_collected(company) || node.kind() == NodeKind::PassThrough {
+            return None;
+        }
+        let travel = shortest.get(company)?.0;
+        let revisits = way_to(shortest, company)?.iter().filter(|c| state.has_visited(c)).count();
+        if !state.rules.may_collect(state.ttl - revisits as i32) {
+            return None;
+        }
+        let arrival = state.elapsed + travel;
+        let waiting = node.collection_start(arrival)? - arrival;
+        if travel + waiting + node.cost > state.time_left {
+            return None;
+        }
+        let value = maximiser.value_model.marginal_value(node, &state.last_companies, &maximiser.nodes);
+        Some(node::ratio(value, travel + waiting + node.cost))
+    }
+
+    /// The target from where we are now, planned once per position and time.
+    fn plan(&self, maximiser: &Maximizer) -> Option<Target> {
+        let state = &maximiser.state;
+        let now = Some((state.current_company.clone(), state.elapsed));
+        if *self.planned_at.borrow() == now {
+            return self.target.borrow().clone();
+        }
+        let (shortest, company) = if state.rules.revisits == Revisits::Free {
+            let shortest = shortest_paths(&maximiser.relationships, &state.current_company);
+            let company = self.choose(&shortest, maximiser);
+            (shortest, company)
+        } else {
+            // Revisits use up the ttl, so ways through new companies come first.
+            let shortest = shortest_paths_through(&maximiser.relationships, &state.current_company,
+                                                  |c| !state.has_visited(c));
+            match self.choose(&shortest, maximiser) {
+                Some(company) => (shortest, Some(company)),
+                None => {
+                    let shortest = shortest_paths(&maximiser.relationships, &state.current_company);
+                    let company = self.choose(&shortest, maximiser);
+                    (shortest, company)
+                }
+            }
+        };
+        let target = company.and_then(|(company, score)| {
+            let next = way_to(&shortest, &company)?.into_iter().next()?;
+            let hop = find_path(&maximiser.relationships, &state.current_company, &next)?.cost;
+            Some(Target { travel: shortest[&company].0, company, next, hop, score })
+        });
+        *self.target.borrow_mut() = target.clone();
+        *self.planned_at.borrow_mut() = now;
+        target
+    }
+
+    /// The current target if it can still be collected, otherwise the best company to head for,
+    /// with its appraisal.
+    fn choose(&self, shortest: &Shortest, maximiser: &Maximizer) -> Option<(String, f64)> {
+        let kept = self.target.borrow().as_ref()
+            .and_then(|t| self.appraise(&t.company, shortest, maximiser).map(|score| (t.company.clone(), score)));
+        kept.or_else(|| {
+            let mut names: Vec<&String> = shortest.keys().collect();
+            names.sort();
+            self.expanded.set(self.expanded.get() + names.len() as u64);
+            names.into_iter()
+                .filter_map(|name| Some((name, self.appraise(name, shortest, maximiser)?)))
+                .fold(None, |best: Option<(&String, f64)>, (name, score)| match best {
+                    Some((_, best_score)) if best_score >= score => best,
+                    _ => Some((name, score)),
+                })
+                .map(|(name, score)| (name.clone(), score))
+        })
+    }
+}
+
+impl CollectionAlgorithm for ShortestPathGreedy {
+    fn name(&self) -> &str {
+        "Shortest path greedy"
+    }
+
+    fn path_score(&self, path: &Path, maximiser: &Maximizer) -> f64 {
+        match self.plan(maximiser) {
+            Some(target) if path.to == target.next && path.cost == target.hop => target.score,
+            _ => 0.0,
+        }
+    }
+
+    fn should_collect(&self, company_name: &str, maximiser: &Maximizer) -> bool {
+        let target = self.plan(maximiser);
+        match &target {
+            Some(target) if target.company == company_name => true,
+            _ => {
+                let node = &maximiser.nodes[company_name];
+                // The time to the target is counted from here, a little more than from the hop.
+                let fits = target.as_ref().is_none_or(|t| {
+                    t.travel + node.cost + maximiser.nodes[&t.company].cost <= maximiser.state.time_left
+                });
+                fits && maximiser.value_per_cost(company_name, &maximiser.state.last_companies) >= self.collect_limit
+            }
+        }
+    }
+
+    fn nodes_expanded(&self) -> u64 {
+        self.expanded.get()
+    }
+
+    fn collect_limit(&self) -> Option<f64> {
+        Some(self.collect_limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{Node, Relationship, Relationships, State, Subnets, TimeWindow};
+    use crate::rules::Rules;
+    use crate::value::Additive;
+
+    fn maximizer(budget: i64) -> Maximizer {
+        // Rich is worth the most but far, Cheap is on the way and Poor next door.
+        let nodes: HashMap<String, Node> = vec![Node::new("Neo4j".to_string(), 0, 0),
+                                                Node::new("Poor".to_string(), 10, 50),
+                                                Node::new("Cheap".to_string(), 40, 10),
+                                                Node::new("Rich".to_string(), 900, 100)]
+            .into_iter().map(|n| (n.name.clone(), n)).collect();
+        let path = |to: &str, cost| Path { to: to.to_string(), cost };
+        let mut relationships = Relationships::new();
+        relationships.insert("Neo4j".to_string(), Relationship { paths: vec![path("Poor", 5), path("Cheap", 60), path("Rich", 500)] });
+        relationships.insert("Poor".to_string(), Relationship { paths: vec![path("Neo4j", 5)] });
+        relationships.insert("Cheap".to_string(), Relationship { paths: vec![path("Rich", 60), path("Neo4j", 60)] });
+        relationships.insert("Rich".to_string(), Relationship { paths: vec![path("Cheap", 60), path("Poor", 300)] });
+        let subnets = Subnets::new(&relationships, &nodes);
+        let mut maximizer = Maximizer::new(State::starting("Neo4j".to_string(), budget, 4), nodes, relationships,
+                                           Box::new(ShortestPathGreedy::new(0.75)), subnets, Box::new(Additive {}));
+        maximizer.verbose = false;
+        maximizer
+    }
+
+    #[test]
+    fn heads_for_the_best_company_the_shortest_way() {
+        let mut maximizer = maximizer(400);
+        maximizer.collect();
+        let route: Vec<String> = maximizer.state.last_companies.iter().map(|a| a.to_string()).collect();
+        assert_eq!(route[..3], ["(Neo4j)", "(Cheap:Collected)", "(Rich:Collected)"]);
+        assert_eq!(maximizer.state.score, 940);
+    }
+
+    #[test]
+    fn settles_for_what_fits_in_the_time() {
+        // Rich no longer fits, Cheap is worth more per time than Poor.
+        let mut maximizer = maximizer(100);
+        maximizer.collect();
+        assert_eq!(maximizer.state.last_companies[1].to_string(), "(Cheap:Collected)");
+        assert_eq!(maximizer.state.score, 40);
+    }
+
+    #[test]
+    fn scores_paths_with_the_waiting_time() {
+        // Rich opens at 300, so after 120 on the way there is 180 to wait before the 100 collecting.
+        let mut maximizer = maximizer(400);
+        let rich = maximizer.nodes["Rich"].clone().with_window(TimeWindow::new(300, 1000));
+        maximizer.nodes.insert("Rich".to_string(), rich);
+        let to_cheap = maximizer.relationships["Neo4j"].paths[1].clone();
+        assert_eq!(maximizer.algorithm.path_score(&to_cheap, &maximizer), 900.0 / 400.0);
+        assert_eq!(maximizer.algorithm.path_score(&maximizer.relationships["Neo4j"].paths[0].clone(), &maximizer), 0.0);
+    }
+
+    #[test]
+    fn takes_the_shortest_way_when_revisits_are_free() {
+        // From Poor the only way on is back through Neo4j, visited at the start.
+        let mut maximizer = maximizer(400);
+        maximizer.state.rules = Rules::official();
+        let nodes = maximizer.nodes.clone();
+        maximizer.state.goto(&maximizer.relationships["Neo4j"].paths[0].clone(), &nodes, &Additive {}, false);
+        let back = maximizer.relationships["Poor"].paths[0].clone();
+        // Rich is 5 + 60 + 60 away and takes 100 to collect.
+        assert_eq!(maximizer.algorithm.path_score(&back, &maximizer), 900.0 / 225.0);
+    }
+}
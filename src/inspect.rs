@@ -0,0 +1,306 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+use serde_json::{json, Value};
+
+use crate::{Node, Relationships};
+use crate::formats::sorted_names;
+use crate::graphs::travel_times;
+
+/// Spread of the finite values per cost, free and pass-through companies are counted apart.
+pub struct Distribution {
+    min: f64,
+    lower_quartile: f64,
+    median: f64,
+    upper_quartile: f64,
+    max: f64,
+    infinite: usize,
+    negative_infinite: usize,
+}
+
+impl Distribution {
+    fn of(mut values: Vec<f64>) -> Distribution {
+        let infinite = values.iter().filter(|v| **v == f64::INFINITY).count();
+        let negative_infinite = values.iter().filter(|v| **v == f64::NEG_INFINITY).count();
+        values.retain(|v| v.is_finite());
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let at = |q: f64| match values.len() {
+            0 => 0.0,
+            n => values[((n - 1) as f64 * q).round() as usize],
+        };
+        Distribution {
+            min: at(0.0), lower_quartile: at(0.25), median: at(0.5), upper_quartile: at(0.75), max: at(1.0),
+            infinite, negative_infinite,
+        }
+    }
+}
+
+/// Statistics of a loaded graph and the things in it which trip up the solvers.
+pub struct Inspection {
+    start: String,
+    nodes: usize,
+    edges: usize,
+    out_degrees: BTreeMap<usize, usize>,
+    in_degrees: BTreeMap<usize, usize>,
+    components: Vec<Vec<String>>,
+    unreachable: Vec<String>,
+    // Farthest travel time among the companies reached and how many those are.
+    eccentricities: Vec<(String, i64, usize)>,
+    diameter: Option<(String, String, i64)>,
+    value_per_cost: Distribution,
+    self_loops: Vec<String>,
+    // From, to, cost there and cost back if there is a path back at all.
+    asymmetric: Vec<(String, String, i64, Option<i64>)>,
+    no_outgoing: Vec<String>,
+    unknown_targets: Vec<(String, String)>,
+}
+
+/// Strongly connected components, largest first (Kosaraju, iterative).
+fn components(names: &[&String], successors: &HashMap<&String, Vec<&String>>) -> Vec<Vec<String>> {
+    let mut predecessors: HashMap<&String, Vec<&String>> = HashMap::new();
+    for (from, tos) in successors {
+        for to in tos {
+            predecessors.entry(*to).or_default().push(*from);
+        }
+    }
+
+    let mut visited = HashSet::new();
+    let mut order = vec![];
+    for name in names {
+        if !visited.insert(*name) {
+            continue;
+        }
+        let mut stack = vec![(*name, 0)];
+        while let Some((node, next)) = stack.pop() {
+            match successors.get(node).and_then(|s| s.get(next)) {
+                Some(child) => {
+                    stack.push((node, next + 1));
+                    if visited.insert(*child) {
+                        stack.push((*child, 0));
+                    }
+                }
+                None => order.push(node),
+            }
+        }
+    }
+
+    let mut assigned = HashSet::new();
+    let mut components = vec![];
+    for name in order.into_iter().rev() {
+        if !assigned.insert(name) {
+            continue;
+        }
+        let mut component = vec![name.clone()];
+        let mut stack = vec![name];
+        while let Some(node) = stack.pop() {
+            for pred in predecessors.get(node).into_iter().flatten() {
+                if assigned.insert(*pred) {
+                    component.push((*pred).clone());
+                    stack.push(*pred);
+                }
+            }
+        }
+        component.sort();
+        components.push(component);
+    }
+    components.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+    components
+}
+
+pub fn inspect(nodes: &HashMap<String, Node>, relationships: &Relationships, start: &str) -> Inspection {
+    let names = sorted_names(nodes);
+    let mut successors: HashMap<&String, Vec<&String>> = HashMap::new();
+    let mut in_degree: HashMap<&String, usize> = HashMap::new();
+    let mut self_loops = vec![];
+    let mut asymmetric = vec![];
+    let mut no_outgoing = vec![];
+    let mut unknown_targets = vec![];
+    let mut edges = 0;
+
+    for name in &names {
+        let paths = relationships.get(*name).map(|rel| &rel.paths[..]).unwrap_or_default();
+        if paths.is_empty() {
+            no_outgoing.push((*name).clone());
+        }
+        for path in paths {
+            edges += 1;
+            if &path.to == *name {
+                self_loops.push((*name).clone());
+            }
+            let Some((to, _)) = nodes.get_key_value(&path.to) else {
+                unknown_targets.push(((*name).clone(), path.to.clone()));
+                continue;
+            };
+            successors.entry(*name).or_default().push(to);
+            *in_degree.entry(to).or_default() += 1;
+            let back = relationships.get(to).iter().flat_map(|rel| &rel.paths)
+                .filter(|p| &p.to == *name).map(|p| p.cost).min();
+            if back != Some(path.cost) {
+                asymmetric.push(((*name).clone(), path.to.clone(), path.cost, back));
+            }
+        }
+    }
+
+    let mut out_degrees = BTreeMap::new();
+    let mut in_degrees = BTreeMap::new();
+    for name in &names {
+        *out_degrees.entry(successors.get(*name).map_or(0, |s| s.len())).or_default() += 1;
+        *in_degrees.entry(in_degree.get(*name).copied().unwrap_or_default()).or_default() += 1;
+    }
+
+    let mut eccentricities = vec![];
+    let mut diameter: Option<(String, String, i64)> = None;
+    let mut unreachable = vec![];
+    for name in &names {
+        let times = travel_times(relationships, name);
+        let mut farthest: Option<(&String, i64)> = None;
+        for other in &names {
+            if let Some(time) = times.get(*other) {
+                if farthest.is_none_or(|(_, t)| *time > t) {
+                    farthest = Some((other, *time));
+                }
+            } else if name.as_str() == start {
+                unreachable.push((*other).clone());
+            }
+        }
+        let reached = names.iter().filter(|other| times.contains_key(**other)).count();
+        eccentricities.push(((*name).clone(), farthest.map_or(0, |(_, t)| t), reached));
+        if let Some((to, time)) = farthest {
+            if diameter.as_ref().is_none_or(|(_, _, t)| time > *t) {
+                diameter = Some(((*name).clone(), to.clone(), time));
+            }
+        }
+    }
+    if !nodes.contains_key(start) {
+        unreachable = names.iter().map(|n| (*n).clone()).collect();
+    }
+
+    Inspection {
+        start: start.to_string(),
+        nodes: nodes.len(),
+        edges,
+        out_degrees,
+        in_degrees,
+        components: components(&names, &successors),
+        unreachable,
+        eccentricities,
+        diameter,
+        value_per_cost: Distribution::of(nodes.values().map(|n| n.value_per_cost()).collect()),
+        self_loops,
+        asymmetric,
+        no_outgoing,
+        unknown_targets,
+    }
+}
+
+fn finite(value: f64) -> Value {
+    if value.is_finite() { json!(value) } else { json!(value.to_string()) }
+}
+
+impl Inspection {
+    pub fn to_json(&self) -> Value {
+        let degrees = |d: &BTreeMap<usize, usize>| d.iter()
+            .map(|(degree, count)| json!({"degree": degree, "count": count})).collect::<Vec<_>>();
+        let d = &self.value_per_cost;
+        json!({
+            "start": self.start,
+            "nodes": self.nodes,
+            "edges": self.edges,
+            "out_degrees": degrees(&self.out_degrees),
+            "in_degrees": degrees(&self.in_degrees),
+            "strongly_connected_components": self.components,
+            "unreachable_from_start": self.unreachable,
+            "eccentricities": self.eccentricities.iter()
+                .map(|(name, e, reached)| json!({"company": name, "eccentricity": e, "reaches": reached}))
+                .collect::<Vec<_>>(),
+            "diameter": self.diameter.as_ref().map(|(from, to, time)| json!({"from": from, "to": to, "time": time})),
+            "value_per_cost": {
+                "min": finite(d.min), "lower_quartile": finite(d.lower_quartile), "median": finite(d.median),
+                "upper_quartile": finite(d.upper_quartile), "max": finite(d.max),
+                "infinite": d.infinite, "negative_infinite": d.negative_infinite,
+            },
+            "self_loops": self.self_loops,
+            "asymmetric_edges": self.asymmetric.iter()
+                .map(|(from, to, cost, back)| json!({"from": from, "to": to, "timePrice": cost, "back": back}))
+                .collect::<Vec<_>>(),
+            "no_outgoing": self.no_outgoing,
+            "unknown_targets": self.unknown_targets.iter()
+                .map(|(from, to)| json!({"from": from, "to": to})).collect::<Vec<_>>(),
+        })
+    }
+}
+
+fn list(items: &[String]) -> String {
+    if items.is_empty() { "none".to_string() } else { items.join(", ") }
+}
+
+impl Display for Inspection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let degrees = |d: &BTreeMap<usize, usize>| d.iter()
+            .map(|(degree, count)| format!("{}: {}", degree, count)).collect::<Vec<_>>().join(", ");
+        let d = &self.value_per_cost;
+        writeln!(f, "{:<28} {}", "Companies", self.nodes)?;
+        writeln!(f, "{:<28} {}", "Paths", self.edges)?;
+        writeln!(f, "{:<28} {}", "Out degree (degree: count)", degrees(&self.out_degrees))?;
+        writeln!(f, "{:<28} {}", "In degree (degree: count)", degrees(&self.in_degrees))?;
+        writeln!(f, "{:<28} {} (sizes {})", "Strong components", self.components.len(),
+                 self.components.iter().map(|c| c.len().to_string()).collect::<Vec<_>>().join(", "))?;
+        writeln!(f, "{:<28} {}", format!("Unreachable from {}", self.start), list(&self.unreachable))?;
+        match &self.diameter {
+            Some((from, to, time)) => writeln!(f, "{:<28} {} ({} to {})", "Diameter (travel time)", time, from, to)?,
+            None => writeln!(f, "{:<28} none", "Diameter (travel time)")?,
+        }
+        writeln!(f, "{:<28} min {:.3}, q1 {:.3}, median {:.3}, q3 {:.3}, max {:.3}, free {}, never {}",
+                 "Value per cost", d.min, d.lower_quartile, d.median, d.upper_quartile, d.max,
+                 d.infinite, d.negative_infinite)?;
+        writeln!(f, "{:<28} {}", "Self loops", list(&self.self_loops))?;
+        writeln!(f, "{:<28} {}", "Asymmetric paths", self.asymmetric.len())?;
+        for (from, to, cost, back) in &self.asymmetric {
+            match back {
+                Some(back) => writeln!(f, "    {} -> {} takes {}, back takes {}", from, to, cost, back)?,
+                None => writeln!(f, "    {} -> {} takes {}, no way back", from, to, cost)?,
+            }
+        }
+        writeln!(f, "{:<28} {}", "No outgoing paths", list(&self.no_outgoing))?;
+        writeln!(f, "{:<28} {}", "Paths to unknown companies",
+                 list(&self.unknown_targets.iter().map(|(a, b)| format!("{} -> {}", a, b)).collect::<Vec<_>>()))?;
+        writeln!(f, "Eccentricity (travel time to the farthest reachable company):")?;
+        for (name, eccentricity, reached) in &self.eccentricities {
+            if *reached == self.nodes {
+                writeln!(f, "    {:<24} {}", name, eccentricity)?;
+            } else {
+                writeln!(f, "    {:<24} {} (reaches {} of {})", name, eccentricity, reached, self.nodes)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Path, Relationship};
+
+    #[test]
+    fn finds_components_loops_and_dead_ends() {
+        let nodes: HashMap<String, Node> = ["A", "B", "C", "D"].iter()
+            .map(|n| (n.to_string(), Node::new(n.to_string(), 10, 5))).collect();
+        let path = |to: &str, cost| Path{ to: to.to_string(), cost };
+        let mut relationships = Relationships::new();
+        relationships.insert("A".to_string(), Relationship{ paths: vec![path("B", 3), path("A", 1)] });
+        relationships.insert("B".to_string(), Relationship{ paths: vec![path("A", 4), path("C", 2), path("E", 1)] });
+        relationships.insert("C".to_string(), Relationship{ paths: vec![] });
+
+        let inspection = inspect(&nodes, &relationships, "A");
+        assert_eq!(inspection.edges, 5);
+        assert_eq!(inspection.components[0], vec!["A".to_string(), "B".to_string()]);
+        assert_eq!(inspection.components.len(), 3);
+        assert_eq!(inspection.unreachable, vec!["D".to_string()]);
+        assert_eq!(inspection.self_loops, vec!["A".to_string()]);
+        assert_eq!(inspection.no_outgoing, vec!["C".to_string(), "D".to_string()]);
+        assert_eq!(inspection.unknown_targets, vec![("B".to_string(), "E".to_string())]);
+        assert_eq!(inspection.asymmetric.len(), 3);
+        assert_eq!(inspection.diameter, Some(("A".to_string(), "C".to_string(), 5)));
+        assert_eq!(inspection.eccentricities[0], ("A".to_string(), 5, 3));
+    }
+}
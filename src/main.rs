@@ -4,6 +4,33 @@ mod graphs;
 mod path;
 mod state;
 mod action;
+mod companies;
+mod rng;
+mod rules;
+mod distribution;
+mod monte_carlo;
+mod cli;
+mod online;
+mod value;
+mod dot;
+mod route;
+mod report;
+mod formats;
+mod apoc;
+mod cypher;
+mod csv;
+mod graphml;
+mod inspect;
+mod generator;
+mod compare;
+mod tune;
+mod greedy;
+mod config;
+mod explain;
+mod polish;
+mod milp;
+#[cfg(feature = "neo4j")]
+mod bolt;
 
 use subnets::*;
 use graphs::*;
@@ -11,48 +38,115 @@ use path::*;
 use node::*;
 use action::*;
 use state::*;
+use value::ValueModel;
 
+use std::cell::{Cell, OnceCell};
 use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Formatter};
-use serde_json;
 use std::fs::{File};
-use std::io;
-use std::io::Read;
+use std::io::{self, Read};
 
 const TTL_PER_ITERATION: i32 = 4;
 const COLLECT_LIMIT: f64 = 0.75;
-
-struct Results {
-    total: f64,
-    cost: f64,
-}
+const MONTE_CARLO_RUNS: usize = 10000;
+const MONTE_CARLO_SEED: u64 = 42;
 
 struct Maximizer {
     state: State,
     nodes: HashMap<String, Node>,
     relationships: Relationships,
     algorithm: Box<dyn CollectionAlgorithm>,
-    subnets: Subnets<f64>
+    subnets: Subnets<f64>,
+    value_model: Box<dyn ValueModel>,
+    verbose: bool, // Print every candidate and step while collecting.
+    explanations: Option<Vec<explain::Decision>> // Why each step was taken, if asked for.
 }
 
 
 trait CollectionAlgorithm{
     fn name(&self) -> &str;
     fn path_score(&self, path: &Path, maximiser: &Maximizer) -> f64; // Shall find the optimal path and return it
-    fn should_collect(&self, company_name: &String, maximiser: &Maximizer) -> bool; // Returns true if node on given company name should be collected
-    fn set_current_position(&mut self, current_position: String){}
+    // The score of path_score with the moves after the path it expects, for algorithms which look ahead.
+    fn path_line(&self, path: &Path, maximiser: &Maximizer) -> (f64, Vec<Action>) {
+        (self.path_score(path, maximiser), vec![])
+    }
+    fn should_collect(&self, company_name: &str, maximiser: &Maximizer) -> bool; // Returns true if node on given company name should be collected
+    fn set_current_position(&mut self, _current_position: String){}
+    fn nodes_expanded(&self) -> u64 { 0 } // States or paths evaluated so far, for benchmarking.
+    fn collect_limit(&self) -> Option<f64> { None } // Least swag per time collected, if there is one.
+    // Told the path taken and the moves expected after it, returns those to make right away
+    // without searching again.
+    fn planned_moves(&mut self, _path: &Path, _line: Vec<Action>) -> Vec<Action> { vec![] }
+}
+
+/// Optimistic score of the look ahead, to skip moves which can not beat the best line found.
+/// Every move collecting swag but the last, with its travel, fits in the rest of the depth, so at
+/// most the swag of a fractional knapsack of that size plus the largest swag is still to come.
+struct SwagBound {
+    companies: Vec<(String, i64, i64)>, // Name, most swag and time with the shortest way in, by swag per time.
+    by_swag: Vec<(String, i64)>,
+}
+
+impl SwagBound {
+    fn new(maximiser: &Maximizer, collect_limit: f64) -> SwagBound {
+        let mut way_in: HashMap<&String, i64> = HashMap::new();
+        for path in maximiser.relationships.values().flat_map(|rel| &rel.paths) {
+            let time = way_in.entry(&path.to).or_insert(path.cost);
+            *time = (*time).min(path.cost);
+        }
+        let mut companies: Vec<(String, i64, i64)> = maximiser.nodes.values()
+            .filter(|node| way_in.contains_key(&node.name))
+            .map(|node| (node.name.clone(), maximiser.value_model.max_value(node), node.cost))
+            .filter(|(_, value, cost)| *value > 0 && node::ratio(*value, *cost) >= collect_limit)
+            .map(|(name, value, cost)| {
+                let time = cost + way_in[&name].max(0);
+                (name, value, time)
+            })
+            .collect();
+        companies.sort_by(|a, b| node::ratio(b.1, b.2).total_cmp(&node::ratio(a.1, a.2)).then(a.0.cmp(&b.0)));
+        let mut by_swag: Vec<(String, i64)> = companies.iter().map(|c| (c.0.clone(), c.1)).collect();
+        by_swag.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        SwagBound { companies, by_swag }
+    }
+
+    fn upper(&self, state: &State, depth_left: i64) -> i64 {
+        let mut room = depth_left.min(state.time_left).max(0);
+        let largest = self.by_swag.iter().find(|(name, _)| !state.has_collected(name)).map_or(0, |c| c.1);
+        let mut score = state.score + largest;
+        for (name, value, cost) in &self.companies {
+            if state.has_collected(name) {
+                continue;
+            }
+            if *cost <= room {
+                score += value;
+                room -= cost;
+            } else {
+                score += (value * room + cost - 1) / cost;
+                break;
+            }
+        }
+        score
+    }
 }
 
 struct DepthSearchAlgo {
     max_depth: i64, // Maximum cost depth!
-    current_real_location: String
+    current_real_location: String,
+    collect_limit: f64, // Least swag per time worth collecting.
+    ttl: i32, // Revisits allowed in one look ahead.
+    commit: usize, // Most moves made per search when the plan holds.
+    plan: Vec<Action>, // The moves expected after the last one, searched first.
+    bound: OnceCell<SwagBound>, // Built by the first search, the graph and value model stay the same.
+    expanded: Cell<u64>
 }
 
 impl DepthSearchAlgo {
     fn new(max_depth: i64, current_real_position: String) -> Self{
-        Self { max_depth: max_depth, current_real_location: current_real_position}
+        Self { max_depth, current_real_location: current_real_position, collect_limit: COLLECT_LIMIT,
+               ttl: TTL_PER_ITERATION, commit: 1, plan: vec![], bound: OnceCell::new(), expanded: Cell::new(0)}
     }
 
+    #[allow(dead_code)]
     fn path_value(&self, path: &Path, maximiser: &Maximizer, visited: &HashSet<String>) -> f64 {
         if maximiser.collect_here_with_visited(&path.to, visited) {
             path.value_per_cost(&maximiser.nodes)
@@ -61,37 +155,67 @@ impl DepthSearchAlgo {
         }
     }
 
-    fn should_collect_own(&self, state: &State, company_name: &String, maximizer: &Maximizer) -> bool {
-        !state.last_companies.contains(
-            &Action::new(company_name.clone(), true)
-        ) && self.should_collect(company_name, maximizer)
+    fn should_collect_own(&self, state: &State, company_name: &str, maximizer: &Maximizer) -> bool {
+        !state.has_collected(company_name) && self.worth_collecting(company_name, &state.last_companies, maximizer)
+    }
+
+    fn worth_collecting(&self, company_name: &str, history: &[Action], maximiser: &Maximizer) -> bool {
+        let collect_worth = maximiser.value_per_cost(company_name, history);
+        // 0.75 gives 4987 at depth 8 same with 0.8 same 0.7, 4976 with 0.9 | 4993 with 0.65, 0.6 much worse (4.4k)
+        collect_worth >= self.collect_limit
     }
 
-    fn recursive_collector(&self, maximiser: &Maximizer, depth: i64, state: State, start_time: i64) -> i64 {
+    /// Best score reachable from `state` before the time left falls to `stop_at`, with the moves
+    /// reaching it (the principal variation) from the last to the first. The state is searched
+    /// in place and left as it was. The moves of `hint` are tried first, so `best`, the best
+    /// score found so far, rises early and more moves are skipped by the bound. Those return
+    /// `i64::MIN`.
+    fn recursive_collector(&self, maximiser: &Maximizer, stop_at: i64, state: &mut State,
+                           hint: &[Action], bound: &SwagBound, best: &mut i64) -> (i64, Vec<Action>) {
+        self.expanded.set(self.expanded.get() + 1);
 
-        if depth <= (start_time - state.time_left) || state.time_left < 0 || state.ttl <= 0 {
-            state.score
+        // Out of time nothing more can be reached, searching on would only burn the ttl.
+        if state.time_left <= stop_at || state.time_left < 0 || state.ttl <= 0 || state.ended() {
+            *best = (*best).max(state.score);
+            (state.score, vec![])
+        } else if bound.upper(state, state.time_left - stop_at) <= *best {
+            (i64::MIN, vec![])
         } else {
             // It is not finished recursing:
 
-            let paths = maximiser.paths_from_company(&state.current_company);
-            let score: i64 = paths.iter().map( |path| {
-                if &path.to != &state.current_company {
-                    let mut next_state = state.clone();
-                    next_state.goto(&path, &maximiser.nodes,
-                                    self.should_collect_own(&next_state,
-                                                            &path.to,
-                                                            maximiser));
-                    self.recursive_collector(maximiser,
-                                             depth,
-                                             next_state,
-                                             start_time) as i64
+            let mut paths = maximiser.paths_from_company(&state.current_company);
+            let hinted = hint.first().and_then(|next| paths.iter().position(|p| p.to == next.company));
+            if let Some(i) = hinted {
+                let path = paths.remove(i);
+                paths.insert(0, path);
+            }
+            let mut found: (i64, Vec<Action>, Option<(&Path, bool)>) = (i64::MIN, vec![], None);
+            for (i, path) in paths.into_iter().enumerate() {
+                let (score, line, step) = if path.to != state.current_company {
+                    let collect = self.should_collect_own(state, &path.to, maximiser);
+                    let undo = state.apply(path, &maximiser.nodes, maximiser.value_model.as_ref(), collect);
+                    let collected = collect && state.has_collected(&path.to);
+                    let hint = if i == 0 && hinted.is_some() { &hint[1..] } else { &[] };
+                    let (score, line) = self.recursive_collector(maximiser,
+                                                                 stop_at,
+                                                                 state,
+                                                                 hint, bound, best);
+                    state.undo(undo);
+                    (score, line, Some((path, collected)))
                 } else {
-                    0
+                    (0, vec![], None)
+                };
+                // The last of equally good moves wins.
+                if score >= found.0 {
+                    found = (score, line, step);
                 }
-            }).max().unwrap();
+            }
 
-            score
+            let (score, mut line, step) = found;
+            if let Some((path, collected)) = step {
+                line.push(Action::new(path.to.clone(), collected));
+            }
+            (score, line)
 
         }
     }
@@ -103,28 +227,60 @@ impl CollectionAlgorithm for DepthSearchAlgo {
     }
 
     fn path_score(&self, path: &Path, maximiser: &Maximizer) -> f64 {
+        self.path_line(path, maximiser).0
+    }
+
+    fn path_line(&self, path: &Path, maximiser: &Maximizer) -> (f64, Vec<Action>) {
         let mut  state = maximiser.state.clone();
-        state.goto(path, &maximiser.nodes, maximiser.collect_here(&path.to));
-        let time_left = state.time_left.clone();
-        state.ttl = TTL_PER_ITERATION;
-        self.recursive_collector(maximiser, self.max_depth, state, time_left) as f64
+        state.goto(path, &maximiser.nodes, maximiser.value_model.as_ref(), maximiser.collect_here(&path.to));
+        let time_left = state.time_left;
+        state.ttl = self.ttl;
+        let hint = match self.plan.first() {
+            Some(next) if next.company == path.to => &self.plan[1..],
+            _ => &[],
+        };
+        let bound = self.bound.get_or_init(|| SwagBound::new(maximiser, self.collect_limit));
+        let mut best = i64::MIN;
+        let (score, mut line) = self.recursive_collector(maximiser, time_left - self.max_depth, &mut state,
+                                                         hint, bound, &mut best);
+        line.reverse();
+        (score as f64, line)
     }
 
-    fn should_collect(&self, company_name: &String, maximiser: &Maximizer) -> bool {
-        let node = maximiser.nodes.get(company_name).unwrap();
+    fn planned_moves(&mut self, path: &Path, line: Vec<Action>) -> Vec<Action> {
+        // Only the moves this search and the last agree on are sure enough to make.
+        let agreed = match self.plan.split_first() {
+            Some((next, after)) if next.company == path.to => {
+                after.iter().zip(&line).take_while(|(a, b)| a.company == b.company).count()
+            }
+            _ => 0,
+        };
+        self.plan = line;
+        self.plan.drain(..agreed.min(self.commit - 1)).collect()
+    }
 
-        let collect_worth = node.value_per_cost();
-        // 0.75 gives 4987 at depth 8 same with 0.8 same 0.7, 4976 with 0.9 | 4993 with 0.65, 0.6 much worse (4.4k)
-        collect_worth >= COLLECT_LIMIT
+    fn should_collect(&self, company_name: &str, maximiser: &Maximizer) -> bool {
+        self.worth_collecting(company_name, &maximiser.state.last_companies, maximiser)
     }
 
     fn set_current_position(&mut self, current_position: String) {
         self.current_real_location = current_position;
     }
 
+    fn nodes_expanded(&self) -> u64 {
+        self.expanded.get()
+    }
+
+    fn collect_limit(&self) -> Option<f64> {
+        Some(self.collect_limit)
+    }
+
 }
 
-struct SimpleSearch {}
+struct SimpleSearch {
+    collect_limit: f64,
+    expanded: Cell<u64>
+}
 
 impl CollectionAlgorithm for SimpleSearch {
     fn name(&self) -> &str {
@@ -132,36 +288,90 @@ impl CollectionAlgorithm for SimpleSearch {
     }
 
     fn path_score(&self, path: &Path, maximiser: &Maximizer) -> f64 {
-        if !maximiser.companies_collected_at().contains(&path.to) {
-            path.value_per_cost(&maximiser.nodes)
+        self.expanded.set(self.expanded.get() + 1);
+        if !maximiser.state.has_collected(&path.to) && maximiser.open_on_arrival(path) {
+            maximiser.path_value_per_cost(path)
         } else {
             0.0
         }
     }
 
-    fn should_collect(&self, company_name: &String, maximiser: &Maximizer) -> bool {
+    fn should_collect(&self, company_name: &str, maximiser: &Maximizer) -> bool {
+
+        maximiser.value_per_cost(company_name, &maximiser.state.last_companies) > self.collect_limit &&
+            !maximiser.state.has_collected(company_name)
+    }
+
+    fn nodes_expanded(&self) -> u64 {
+        self.expanded.get()
+    }
 
-        maximiser.nodes.get(company_name).unwrap().value_per_cost() > 0.75 &&
-            !maximiser.companies_collected_at().contains(company_name)
+    fn collect_limit(&self) -> Option<f64> {
+        Some(self.collect_limit)
+    }
+}
+
+/// Builds the algorithm named by `spec`, the name followed by `:`-separated parameters such as
+/// `depth:max_depth=60:collect_limit=0.7:ttl=4`. A bare number is the maximum depth, so
+/// `depth:60` works too. The simple search and the greedy only use `collect_limit`.
+fn algorithm_from_spec(spec: &str, start: String) -> Result<Box<dyn CollectionAlgorithm>, String> {
+    let mut parts = spec.split(':');
+    let name = parts.next().unwrap_or_default();
+    // Depth of 6 yields best result on this dataset
+    let mut depth = DepthSearchAlgo::new(1000, start);
+    let mut simple_limit = 0.75;
+    for part in parts {
+        let (key, value) = part.split_once('=').unwrap_or(("max_depth", part));
+        let invalid = || format!("{} is not a valid {} in {}", value, key, spec);
+        match key {
+            "max_depth" => depth.max_depth = value.parse().map_err(|_| invalid())?,
+            "collect_limit" => {
+                depth.collect_limit = value.parse().map_err(|_| invalid())?;
+                simple_limit = depth.collect_limit;
+            }
+            "ttl" => depth.ttl = value.parse().map_err(|_| invalid())?,
+            "commit" => depth.commit = value.parse().ok().filter(|&commit| commit >= 1).ok_or_else(invalid)?,
+            _ => return Err(format!("Unknown parameter {} in {}, expected max_depth, collect_limit, ttl or commit", key, spec)),
+        }
+    }
+    match name {
+        "simple" => Ok(Box::new(SimpleSearch { collect_limit: simple_limit, expanded: Cell::new(0) })),
+        "depth" => Ok(Box::new(depth)),
+        "greedy" => Ok(Box::new(greedy::ShortestPathGreedy::new(simple_limit))),
+        _ => Err(format!("Unknown algorithm {}, expected depth, depth:N, simple or greedy", spec)),
     }
 }
 
 impl Maximizer {
-    fn new(state: State, nodes: HashMap<String, Node>,
+    fn new(mut state: State, nodes: HashMap<String, Node>,
            relationships: Relationships,
            algorithm: Box<dyn CollectionAlgorithm>,
-           subnets: Subnets<f64>) -> Maximizer {
+           subnets: Subnets<f64>,
+           value_model: Box<dyn ValueModel>) -> Maximizer {
+        state.index(&nodes);
         Maximizer {
             state,
             nodes,
             relationships,
             algorithm,
-            subnets
+            subnets,
+            value_model,
+            verbose: true,
+            explanations: None
         }
     }
 
-    fn companies_collected_at(&self) -> HashSet<String> {
-        self.state.last_companies.iter().filter(|a| a.collected).map(|l| l.company.clone()).collect()
+    /// Swag per time spent collecting at the company after the collections in `history`.
+    fn value_per_cost(&self, company_name: &str, history: &[Action]) -> f64 {
+        let node = self.nodes.get(company_name).unwrap();
+        node::ratio(self.value_model.marginal_value(node, history, &self.nodes), node.cost)
+    }
+
+    /// Swag per time spent going along the path and collecting at its end.
+    fn path_value_per_cost(&self, path: &Path) -> f64 {
+        let node = self.nodes.get(&path.to).unwrap();
+        let value = self.value_model.marginal_value(node, &self.state.last_companies, &self.nodes);
+        node::ratio(value, node.cost + path.cost)
     }
 
     fn paths_from_company(&self, company: &String) -> Vec<&Path> {
@@ -170,25 +380,82 @@ impl Maximizer {
 
     fn collect(&mut self) {
 
-        // ADD STARTING SPOT
+        // ADD STARTING SPOT, unless carrying on with a route started earlier.
 
-        let coll = self.collect_here(&self.state.current_company);
-        self.state.last_companies.push(
-            Action{ company: self.state.current_company.clone(),
-                          collected:  coll});
-        if coll {self.state.force_collect(
-            self.nodes.get(&*self.state.current_company).unwrap())}
+        if self.state.last_companies.is_empty() {
+            let decision = self.decision(vec![], self.state.current_company.clone());
+            let coll = self.collect_here(&self.state.current_company) && self.state.force_collect(
+                self.nodes.get(&*self.state.current_company).unwrap(), &self.nodes, self.value_model.as_ref());
+            self.state.record(
+                Action{ company: self.state.current_company.clone(),
+                              collected:  coll});
+            self.explained(decision);
+        }
         while self.state.time_left > 0 {
             //let test = Path{ to: "".to_string(), cost: 0 };
 
-            let max = self.max_score_collect().expect("No valid path exists.");
-            println!("{}", max.to);
-            println!("{}", self.state.current_company);
+            let (max, line, decision) = {
+                let scored = self.scored_paths();
+                let best = Maximizer::best_path(&scored, &self.state.current_company).expect("No valid path exists.");
+                let (max, line) = (scored[best].0.clone(), scored[best].2.clone());
+                let candidates = match self.explanations {
+                    Some(_) => scored.into_iter().map(|(path, score, line)| explain::Candidate {
+                        to: path.to.clone(), travel: path.cost, score, line
+                    }).collect(),
+                    None => vec![],
+                };
+                let decision = self.decision(candidates, max.to.clone());
+                (max, line, decision)
+            };
+            if self.verbose {
+                println!("{}", max.to);
+                println!("{}", self.state.current_company);
+            }
 
             assert!(max.to != self.state.current_company, "Cant go to yourself.");
             self.goto(max.clone());
+            self.explained(decision);
             self.algorithm.set_current_position(self.state.current_company.clone());
             self.print();
+
+            // Moves the algorithm is sure enough of are made without searching again.
+            for action in self.algorithm.planned_moves(&max, line) {
+                let path = match route::find_path(&self.relationships, &self.state.current_company, &action.company) {
+                    Some(path) if self.state.time_left > 0 => path.clone(),
+                    _ => break,
+                };
+                let decision = self.decision(vec![], path.to.clone())
+                    .map(|decision| explain::Decision { planned: true, ..decision });
+                self.goto(path);
+                self.explained(decision);
+                self.algorithm.set_current_position(self.state.current_company.clone());
+                self.print();
+            }
+        }
+    }
+
+    /// The explanation of going to `chosen` from here, completed by `explained` once it is taken.
+    fn decision(&self, candidates: Vec<explain::Candidate>, chosen: String) -> Option<explain::Decision> {
+        self.explanations.as_ref()?;
+        Some(explain::Decision {
+            step: self.state.last_companies.len(),
+            from: self.state.current_company.clone(),
+            time_left: self.state.time_left,
+            candidates,
+            wanted: self.collect_here(&chosen),
+            already_collected: self.state.has_collected(&chosen),
+            ratio: self.value_per_cost(&chosen, &self.state.last_companies),
+            limit: self.algorithm.collect_limit(),
+            chosen,
+            planned: false,
+            collected: false,
+        })
+    }
+
+    fn explained(&mut self, decision: Option<explain::Decision>) {
+        if let (Some(explanations), Some(mut decision)) = (self.explanations.as_mut(), decision) {
+            decision.collected = self.state.last_companies.last().is_some_and(|a| a.collected);
+            explanations.push(decision);
         }
     }
 
@@ -196,50 +463,62 @@ impl Maximizer {
         self.state.goto(
             &path,
             &self.nodes,
+            self.value_model.as_ref(),
             self.collect_here(&path.to));
     }
 
-    fn print(&self) {
-        println!("{}", &self);
+    fn open_on_arrival(&self, path: &Path) -> bool {
+        self.nodes.get(&path.to).unwrap()
+            .collection_start(self.state.elapsed + path.cost).is_some()
     }
 
-    fn collect_here(&self, company_name: &String) -> bool {
-        !self.state.last_companies.contains(
-            &Action::new(company_name.clone(), true)
-        ) && self.algorithm.should_collect(company_name, &self)
+    fn print(&self) {
+        if self.verbose {
+            println!("{}", &self);
+        }
     }
 
-    fn collect_here_with_other_state(&self, other_state: &State) -> bool {
-        let company_name = &other_state.current_company;
-        !other_state.last_companies.contains(
-            &Action::new(company_name.clone(), true)
-        ) && self.algorithm.should_collect(company_name, &self)
+    fn collect_here(&self, company_name: &str) -> bool {
+        !self.state.has_collected(company_name) && self.algorithm.should_collect(company_name, self)
     }
 
-    fn collect_here_with_visited(&self, company_name: &String, visited: &HashSet<String>) -> bool {
+    #[allow(dead_code)]
+    fn collect_here_with_visited(&self, company_name: &str, visited: &HashSet<String>) -> bool {
         (!visited.contains(company_name)) && self.collect_here(company_name)
     }
 
 
 
     fn max_score_collect(&self) -> Option<&Path> {
-        let mut best_score = 0.0;
-        let mut best_path: Option<&Path> = None;
-        let mut backup_path: Option<&Path> = None;
+        let scored = self.scored_paths();
+        Maximizer::best_path(&scored, &self.state.current_company).map(|best| scored[best].0)
+    }
 
-        for rel in self.relationships.get(&*self.state.current_company) {
-            for path in &rel.paths {
-                //println!("{}", path);
-                let score = self.algorithm.path_score(path, self);
+    /// Every path from here with its score and the moves the algorithm expects after it.
+    fn scored_paths(&self) -> Vec<(&Path, f64, Vec<Action>)> {
+        self.paths_from_company(&self.state.current_company).into_iter().map(|path| {
+            //println!("{}", path);
+            let (score, line) = self.algorithm.path_line(path, self);
+            if self.verbose {
                 println!("{}: {}", path, score);
+            }
+            (path, score, line)
+        }).collect()
+    }
 
-                if score > best_score && &path.to != &self.state.current_company{
-                    best_score = score;
-                    best_path = Some(path);
-                }
-                if backup_path.is_none() {
-                    backup_path = Some(path);
-                }
+    /// Index of the best scored path, the first if none scores.
+    fn best_path(scored: &[(&Path, f64, Vec<Action>)], current_company: &str) -> Option<usize> {
+        let mut best_score = 0.0;
+        let mut best_path: Option<usize> = None;
+        let mut backup_path: Option<usize> = None;
+
+        for (i, (path, score, _)) in scored.iter().enumerate() {
+            if *score > best_score && path.to != current_company{
+                best_score = *score;
+                best_path = Some(i);
+            }
+            if backup_path.is_none() {
+                backup_path = Some(i);
             }
         }
         if best_path.is_none() {
@@ -252,42 +531,365 @@ impl Maximizer {
 
 impl Display for Maximizer {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}: Using algorithm {} | Trip length: {}",
-               self.state, self.algorithm.name(), self.state.last_companies.len())
+        write!(f, "{}: Using algorithm {} | Value model: {} | Rules: {} | Trip length: {}",
+               self.state, self.algorithm.name(), self.value_model.name(), self.state.rules,
+               self.state.last_companies.len())
     }
 }
 
 
+#[cfg(feature = "neo4j")]
+fn load_bolt(args: &cli::Args, address: &str, names: &formats::PropertyNames) -> Result<(HashMap<String, Node>, Relationships), String> {
+    let password = match args.get("password") {
+        Some(password) => password.to_string(),
+        None => std::env::var("NEO4J_PASSWORD").unwrap_or_default(),
+    };
+    let mut client = bolt::BoltClient::connect(address, args.get("user").unwrap_or("neo4j"), &password)?;
+    bolt::load(&mut client, args.get("nodes-query").unwrap_or(bolt::DEFAULT_NODES_QUERY),
+               args.get("paths-query").unwrap_or(bolt::DEFAULT_PATHS_QUERY), names)
+}
+
+#[cfg(not(feature = "neo4j"))]
+fn load_bolt(_: &cli::Args, _: &str, _: &formats::PropertyNames) -> Result<(HashMap<String, Node>, Relationships), String> {
+    Err("Reading from Neo4j needs the neo4j feature, build with --features neo4j.".to_string())
+}
+
 fn main() {
-    let json = read_json();
-    let (nodes, relations) = neo4j_json_to_structures(&json);
+    // Progress of long runs such as tune is logged to stderr, stdout carries the results.
+    tracing_subscriber::fmt().with_writer(io::stderr).init();
+    let args = cli::Args::from_env();
+    let config = config::RunConfig::from_args(&args).unwrap_or_else(|e| panic!("{}", e));
+    let data = config.data.as_str();
+    let format = match &config.format {
+        Some(format) => format.parse().unwrap_or_else(|e: String| panic!("{}", e)),
+        None => formats::Format::from_path(data),
+    };
+    let names = config.names.clone();
+    if let Some(path) = args.get("save-config") {
+        write_output(Some(path), &serde_json::to_string_pretty(&config.to_json()).unwrap());
+    }
+    if args.command == "generate" {
+        let defaults = generator::GeneratorConfig::default();
+        let spread = |key: &str, default| match args.get(key) {
+            Some(spread) => spread.parse().unwrap_or_else(|e: String| panic!("--{}: {}", key, e)),
+            None => default,
+        };
+        let shape = generator::GeneratorConfig {
+            nodes: args.get_or("nodes", defaults.nodes),
+            clusters: args.get_or("clusters", defaults.clusters),
+            density: args.get_or("density", defaults.density),
+            inter_density: args.get_or("inter-density", defaults.inter_density),
+            swag: spread("swag", defaults.swag),
+            time_price: spread("time-price", defaults.time_price),
+            path_time: spread("path-time", defaults.path_time),
+            negative: args.get_or("negative", defaults.negative),
+            symmetric: args.get("asymmetric").is_none(),
+            start: defaults.start,
+            seed: args.get_or("seed", defaults.seed),
+        };
+        let (nodes, relations) = generator::generate(&shape);
+        let out = config.out.as_deref().unwrap_or("generated.json");
+        let to = match args.get("to") {
+            Some(to) => to.parse().unwrap_or_else(|e: String| panic!("{}", e)),
+            None => formats::Format::from_path(out),
+        };
+        formats::save(out, args.get("edges-out"), &to, &names, &nodes, &relations)
+            .unwrap_or_else(|e| panic!("{}", e));
+        return;
+    }
+
+    if args.command == "compare" {
+        let instances = match args.get("instances") {
+            Some(directory) => compare::load_instances(directory, &names).unwrap_or_else(|e| panic!("{}", e)),
+            None => {
+                let (nodes, relationships) = formats::load(data, config.edges.as_deref(), &format, &names)
+                    .unwrap_or_else(|e| panic!("{}", e));
+                vec![compare::Instance { name: data.to_string(), nodes, relationships }]
+            }
+        };
+        let specs: Vec<String> = args.get("algorithms").unwrap_or("simple,depth:60,depth:150")
+            .split(',').map(|s| s.trim().to_string()).collect();
+        let best_known: HashMap<String, i64> = match args.get("best-known") {
+            Some(path) => read_json(path).as_object().expect("--best-known takes {\"instance\": score}")
+                .iter().filter_map(|(k, v)| Some((k.clone(), v.as_i64()?))).collect(),
+            None => HashMap::new(),
+        };
+        let rows = compare::compare(&instances, &specs, args.get_or("repetitions", 1), &best_known, &config);
+        let out = config.out.as_deref();
+        let table = if out.is_some_and(|o| o.ends_with(".csv")) { compare::to_csv(&rows) } else { compare::to_markdown(&rows) };
+        write_output(out, &table);
+        return;
+    }
+
+    if args.command == "tune" {
+        let parameters: Vec<tune::Parameter> = [("max-depth", "max_depth", true), ("collect-limit", "collect_limit", false), ("ttl", "ttl", true)]
+            .iter().filter_map(|(option, name, integer)| args.get(option).map(|range| tune::Parameter {
+                name: name.to_string(),
+                range: range.parse().unwrap_or_else(|e: String| panic!("--{}: {}", option, e)),
+                integer: *integer,
+            })).collect();
+        let samples = args.get_or("samples", 20);
+        let search = match args.get("search").unwrap_or("grid") {
+            "grid" => tune::Search::Grid { steps: args.get_or("steps", 5) },
+            "random" => tune::Search::Random { samples },
+            "halving" => tune::Search::Halving { samples },
+            other => panic!("Unknown search {}, expected grid, random or halving", other),
+        };
+        let training = compare::load_instances(args.get("train").expect("tune needs --train with a directory of instances"), &names)
+            .unwrap_or_else(|e| panic!("{}", e));
+        let validation = match args.get("validate") {
+            Some(directory) => compare::load_instances(directory, &names).unwrap_or_else(|e| panic!("{}", e)),
+            None => vec![],
+        };
+        let tuned = tune::tune(&config, &parameters, &search, &training, &validation)
+            .unwrap_or_else(|e| panic!("{}", e));
+        println!("Best: {} with training score {:.1} and validation score {:.1} after {} evaluations",
+                 tuned.algorithm, tuned.training_score, tuned.validation_score, tuned.evaluated);
+        // The run config with the tuned algorithm, loadable with --config.
+        let out = config.out.clone().unwrap_or("tuned.json".to_string());
+        let mut json = config::RunConfig { algorithm: tuned.algorithm.clone(), out: None, ..config }.to_json();
+        json["tuning"] = tuned.to_json();
+        write_output(Some(&out), &serde_json::to_string_pretty(&json).unwrap());
+        return;
+    }
+
+    let (nodes, relations) = match &config.bolt {
+        Some(address) => load_bolt(&args, address, &names),
+        None => formats::load(data, config.edges.as_deref(), &format, &names),
+    }.unwrap_or_else(|e| panic!("{}", e));
+    for warning in graphs::validate(&nodes, &relations) {
+        eprintln!("Warning: {}", warning);
+    }
     //println!("{}", nodes["Nuxxcoin"]);
     //println!("{}", relations["Nuxxcoin"]);
 
-    let subnets = subnets::Subnets::new(&relations, &nodes);
+    if args.command == "convert" {
+        let out = config.out.as_deref().expect("convert needs --out");
+        let to = match args.get("to") {
+            Some(to) => to.parse().unwrap_or_else(|e: String| panic!("{}", e)),
+            None => formats::Format::from_path(out),
+        };
+        formats::save(out, args.get("edges-out"), &to, &names, &nodes, &relations)
+            .unwrap_or_else(|e| panic!("{}", e));
+        return;
+    }
+
+    if args.command == "inspect" {
+        let inspection = inspect::inspect(&nodes, &relations, &config.start);
+        if args.get("json").is_some() {
+            write_output(config.out.as_deref(), &format!("{:#}\n", inspection.to_json()));
+        } else {
+            write_output(config.out.as_deref(), &inspection.to_string());
+        }
+        return;
+    }
 
-    println!("{}", subnets);
-    /**subnets.neighbours(subnets.subnets.get(8).unwrap(),
+    let subnets = subnets::Subnets::new(&relations, &nodes);
+    /*subnets.neighbours(subnets.subnets.get(8).unwrap(),
                        &relations).iter().
         for_each(|f| println!("{}", f.0));
-    **/
+    */
 
-    let state = State::new(TTL_PER_ITERATION);
-    let state_start = (&state.current_company).clone();
-    // Depth of 6 yields best result on this dataset
+    let state = config.start_state().unwrap_or_else(|e| panic!("{}", e));
+    let state_start = state.current_company.clone();
+    let budget = state.time_left + state.elapsed;
+    let planning_nodes = match config.quantile {
+        Some(q) => distribution::quantile_nodes(&nodes, q),
+        None => nodes.clone(),
+    };
+    let algorithm = algorithm_from_spec(&config.algorithm, state_start).unwrap_or_else(|e| panic!("{}", e));
+    let value_model = config.value_model(&subnets).unwrap_or_else(|e| panic!("{}", e));
     let mut maximizer = Maximizer::new(state,
-                                       nodes,
+                                       planning_nodes,
                                        relations,
-                                       Box::new(
-                                           DepthSearchAlgo { max_depth: 1000,
-                                               current_real_location: state_start}), subnets);
-    maximizer.collect();
+                                       algorithm, subnets, value_model);
+
+    match args.command.as_str() {
+        "solve" => {
+            println!("{}", maximizer.subnets);
+            if config.explain {
+                maximizer.explanations = Some(vec![]);
+            }
+            maximizer.collect();
+            if config.polish {
+                polish_route(&mut maximizer, &nodes, &config);
+            }
+
+            println!("{}", monte_carlo::evaluate(&maximizer.state.last_companies, &nodes,
+                                                 &maximizer.relationships,
+                                                 maximizer.value_model.as_ref(), budget,
+                                                 config.monte_carlo_runs, config.seed)
+                .unwrap_or_else(|e| panic!("{}", e)));
+            if let Some(decisions) = &maximizer.explanations {
+                println!("{}", explain::to_text(decisions));
+            }
+            if let Some(path) = &config.route_out {
+                // The config goes along so the route can be reproduced.
+                let mut route = route::route_to_json(&maximizer.state, maximizer.algorithm.name());
+                route["config"] = config.to_json();
+                if let Some(decisions) = &maximizer.explanations {
+                    route["explanation"] = explain::to_json(decisions);
+                }
+                write_output(Some(path), &serde_json::to_string_pretty(&route).unwrap());
+            }
+        }
+        "polish" => {
+            let (actions, algorithm) = route_actions(&args, &mut maximizer);
+            maximizer.state.last_companies = actions;
+            polish_route(&mut maximizer, &nodes, &config);
+            let mut route = route::route_to_json(&maximizer.state, &algorithm);
+            route["config"] = config.to_json();
+            write_output(config.route_out.as_deref(), &serde_json::to_string_pretty(&route).unwrap());
+        }
+        "export-milp" => {
+            if config.value != "additive" || config.set_bonus.is_some() {
+                eprintln!("Warning: the MILP counts plain swag, not the {} value model.", config.value);
+            }
+            if maximizer.state.rules != rules::Rules::official() {
+                eprintln!("Warning: the MILP plays by the official rules, not {}.", maximizer.state.rules);
+            }
+            if nodes.values().any(|node| node.window.is_some()) {
+                eprintln!("Warning: the MILP leaves out the opening hours of companies.");
+            }
+            let milp = milp::Milp::new(&maximizer.state, &nodes, &maximizer.relationships)
+                .unwrap_or_else(|e| panic!("{}", e));
+            let out = config.out.as_deref().unwrap_or("instance.lp");
+            let to = match args.get("to") {
+                Some(to) => to.parse().unwrap_or_else(|e: String| panic!("{}", e)),
+                None => milp::MilpFormat::from_path(out),
+            };
+            write_output(Some(out), &milp.write(to));
+        }
+        "import-milp" => {
+            let path = args.get("solution").expect("import-milp needs --solution with the solver's solution file");
+            let text = std::fs::read_to_string(path).expect("Could not read the solution file.");
+            let milp = milp::Milp::new(&maximizer.state, &nodes, &maximizer.relationships)
+                .unwrap_or_else(|e| panic!("{}", e));
+            let values = milp.read_solution(&text).unwrap_or_else(|e| panic!("{}", e));
+            for violation in milp.violations(&values) {
+                eprintln!("Warning: the solution breaks {}", violation);
+            }
+            let actions = milp.route(&values).unwrap_or_else(|e| panic!("{}", e));
+            let (_, state) = route::walk(&actions, maximizer.state.clone(), &nodes, &maximizer.relationships,
+                                         maximizer.value_model.as_ref())
+                .unwrap_or_else(|e| panic!("The solution is not a valid route: {}", e));
+            println!("The solver's objective {} scores {} by the {} rules", milp.objective_value(&values), state.score,
+                     state.rules);
+            if let Some(path) = &config.route_out {
+                let mut route = route::route_to_json(&state, "MILP");
+                route["config"] = config.to_json();
+                write_output(Some(path), &serde_json::to_string_pretty(&route).unwrap());
+            }
+        }
+        "online" => online::run(&mut maximizer, io::stdin().lock(), io::stdout(), args.get("state-out"))
+            .expect("Could not talk to the driver."),
+        "export-dot" => {
+            if !args.get_or("no-route", false) {
+                maximizer.verbose = false;
+                maximizer.collect();
+            }
+            let dot = dot::to_dot(&nodes, &maximizer.relationships, &maximizer.subnets,
+                                  Some(&maximizer.state.last_companies));
+            write_output(config.out.as_deref(), &dot);
+        }
+        "report" => {
+            let (steps, algorithm) = route_steps(&args, &mut maximizer, &nodes, config.state());
+            let html = report::to_html(&steps, &nodes, &maximizer.relationships, &algorithm, budget);
+            write_output(Some(config.out.as_deref().unwrap_or("report.html")), &html);
+        }
+        "export-cypher" => {
+            let route = if args.get_or("no-route", false) {
+                None
+            } else {
+                Some(route_steps(&args, &mut maximizer, &nodes, config.state()))
+            };
+            let route_name = |algorithm: &String| args.get("route-name").unwrap_or(algorithm).to_string();
+            let script = match args.get("load-csv") {
+                // CSV files for the Neo4j import directory and a script loading them.
+                Some(prefix) => {
+                    let (nodes_csv, edges_csv) = csv::structures_to_csv(&nodes, &maximizer.relationships, &names);
+                    let files = cypher::CsvFiles::new(prefix);
+                    write_output(Some(&files.nodes), &nodes_csv);
+                    write_output(Some(&files.edges), &edges_csv);
+                    if let Some((steps, _)) = &route {
+                        write_output(Some(&files.route), &csv::route_to_csv(steps));
+                    }
+                    cypher::load_csv_script(&files, route.as_ref().map(|(_, a)| route_name(a)).as_deref(), &names)
+                }
+                None => {
+                    let mut script = cypher::structures_to_cypher(&nodes, &maximizer.relationships, &names);
+                    if let Some((steps, algorithm)) = &route {
+                        script += &cypher::route_to_cypher(&route_name(algorithm), steps, &names);
+                    }
+                    script
+                }
+            };
+            write_output(config.out.as_deref(), &script);
+        }
+        other => panic!("Unknown command {}, expected solve, polish, online, export-dot, report, export-cypher, export-milp or import-milp", other),
+    }
+}
+
+/// Polishes the route walked by the maximizer, which then ends where the polished route does.
+/// Routes carried on from a resumed one are left alone, their beginning has been walked already.
+fn polish_route(maximizer: &mut Maximizer, nodes: &HashMap<String, Node>, config: &config::RunConfig) {
+    if config.resume.is_some() {
+        eprintln!("Warning: a resumed route is not polished.");
+        return;
+    }
+    let polished = polish::polish(&maximizer.state.last_companies, &config.state(), nodes,
+                                  &maximizer.relationships, maximizer.value_model.as_ref())
+        .expect("The route is not valid on this graph.");
+    println!("Polished from {} to {} with {} moves and {} more collections", polished.before, polished.score,
+             polished.moves, polished.inserted);
+    if polished.route != maximizer.state.last_companies {
+        // The explanation is of the route before.
+        maximizer.explanations = None;
+    }
+    let (_, state) = route::walk(&polished.route, config.state(), nodes, &maximizer.relationships,
+                                 maximizer.value_model.as_ref()).unwrap();
+    maximizer.state = state;
+}
+
+/// The route of `--route route.json`, or of solving now if not given, with the name of the
+/// algorithm which found it.
+fn route_actions(args: &cli::Args, maximizer: &mut Maximizer) -> (Vec<Action>, String) {
+    match args.get("route") {
+        Some(path) => {
+            let json = read_json(path);
+            (route::route_from_json(&json).expect("Could not read the route."),
+             json["algorithm"].as_str().unwrap_or("unknown").to_string())
+        }
+        None => {
+            maximizer.verbose = false;
+            maximizer.collect();
+            (maximizer.state.last_companies.clone(), maximizer.algorithm.name().to_string())
+        }
+    }
+}
+
+/// The replayed route of `--route route.json`, or of solving now if not given, with the name of
+/// the algorithm which found it.
+fn route_steps(args: &cli::Args, maximizer: &mut Maximizer, nodes: &HashMap<String, Node>, start: State) -> (Vec<route::Step>, String) {
+    let (actions, algorithm) = route_actions(args, maximizer);
+    let steps = route::replay(&actions, start, nodes,
+                              &maximizer.relationships, maximizer.value_model.as_ref())
+        .expect("The route is not valid on this graph.");
+    (steps, algorithm)
 }
 
 
 
-fn read_json() -> serde_json::Value {
-    let mut file = File::open("data.json").expect("Could not open the file.");
+/// Writes to the file if given, otherwise to stdout.
+fn write_output(path: Option<&str>, contents: &str) {
+    match path {
+        Some(path) => std::fs::write(path, contents).expect("Could not write the output file."),
+        None => print!("{}", contents),
+    }
+}
+
+fn read_json(path: &str) -> serde_json::Value {
+    let mut file = File::open(path).expect("Could not open the file.");
     let mut data = String::new();
     file.read_to_string(&mut data).expect("Could not read file");
     let json: serde_json::Value = serde_json::from_str(&data).expect("could not parse");
@@ -301,16 +903,15 @@ fn neo4j_json_to_structures(json: &serde_json::Value) -> (HashMap<String, Node>,
 
     let mut resulting_nodes: HashMap<String, Node> = HashMap::new();
     let mut resulting_relationship = Relationships::new();
-    for (k, v) in nodes.as_object().clone().unwrap() {
+    for (k, v) in nodes.as_object().unwrap() {
         let temp_node = v.as_object().unwrap();
-        resulting_nodes.insert(k.clone(),
-                                    Node::new(
-                                        temp_node["name"].as_str().unwrap().parse().unwrap(),
-                                        temp_node["swag"].as_i64().unwrap(),
-                                        temp_node["timePrice"].as_i64().unwrap()));
-    }
-    println!("{}", resulting_nodes.get("company10").unwrap());
-    for (k, v) in relationships.as_object().clone().unwrap() {
+        let node = Node::new(
+            temp_node["name"].as_str().unwrap().parse().unwrap(),
+            temp_node["swag"].as_i64().unwrap(),
+            temp_node["timePrice"].as_i64().unwrap());
+        resulting_nodes.insert(k.clone(), formats::with_optional_properties(node, temp_node));
+    }
+    for (k, v) in relationships.as_object().unwrap() {
         //println!("{}", serde_json::to_string_pretty(v).unwrap());
         //println!("{}", json!(v.as_array().unwrap()[0].as_object().unwrap()));
 
@@ -327,8 +928,232 @@ fn neo4j_json_to_structures(json: &serde_json::Value) -> (HashMap<String, Node>,
 
     let mut resulting_nodes_nice_names: HashMap<String, Node> = HashMap::new();
     for (_, v) in resulting_nodes {
+        // Every company gets a relationship entry, dead ends too.
+        resulting_relationship.entry(v.name.clone()).or_insert(Relationship{ paths: vec![] });
         resulting_nodes_nice_names.insert(v.name.clone(), v.clone());
     }
 
     (resulting_nodes_nice_names, resulting_relationship)
 }
+
+/// The inverse of neo4j_json_to_structures, companies get keys company0, company1... by name.
+fn structures_to_neo4j_json(nodes: &HashMap<String, Node>, relationships: &Relationships) -> serde_json::Value {
+    let names = formats::sorted_names(nodes);
+    let keys: HashMap<&String, String> = names.iter().enumerate()
+        .map(|(i, name)| (*name, format!("company{}", i))).collect();
+
+    let mut json_nodes = serde_json::Map::new();
+    let mut json_relationships = serde_json::Map::new();
+    for name in &names {
+        let node = &nodes[*name];
+        let mut properties = formats::optional_properties(node);
+        properties.insert("name".to_string(), serde_json::Value::from(name.as_str()));
+        properties.insert("swag".to_string(), serde_json::Value::from(node.value));
+        properties.insert("timePrice".to_string(), serde_json::Value::from(node.cost));
+        json_nodes.insert(keys[name].clone(), serde_json::Value::Object(properties));
+
+        let paths: Vec<serde_json::Value> = relationships.get(*name).iter().flat_map(|rel| &rel.paths)
+            .map(|p| serde_json::json!({"to": keys[&p.to], "timePrice": p.cost})).collect();
+        json_relationships.insert(keys[name].clone(), serde_json::Value::from(paths));
+    }
+    serde_json::json!({"nodes": json_nodes, "relationships": json_relationships})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_data() -> (HashMap<String, Node>, Relationships) {
+        formats::load("data.json", None, &formats::Format::Json, &formats::PropertyNames::default()).unwrap()
+    }
+
+    fn maximizer(spec: &str, state: State) -> Maximizer {
+        let (nodes, relationships) = load_data();
+        let algorithm = algorithm_from_spec(spec, state.current_company.clone()).unwrap();
+        let subnets = Subnets::new(&relationships, &nodes);
+        let mut maximizer = Maximizer::new(state, nodes, relationships, algorithm, subnets, Box::new(value::Additive {}));
+        maximizer.verbose = false;
+        maximizer
+    }
+
+    #[test]
+    fn dead_ends_get_a_relationship_entry() {
+        let json = serde_json::json!({
+            "nodes": {
+                "company0": {"name": "Neo4j", "swag": 0, "timePrice": 0},
+                "company1": {"name": "Dovu", "swag": 129, "timePrice": 27},
+                "company2": {"name": "Dead", "swag": 50, "timePrice": 5},
+            },
+            "relationships": {
+                "company0": [{"to": "company1", "timePrice": 5}],
+                "company1": [{"to": "company0", "timePrice": 5}, {"to": "company2", "timePrice": 5}],
+            },
+        });
+        let (nodes, relationships) = neo4j_json_to_structures(&json);
+        assert!(relationships["Dead"].paths.is_empty());
+        assert_eq!(Subnets::new(&relationships, &nodes).subnets.len(), 0);
+    }
+
+    /// Solves data.json from `state`, which may be partway along a route.
+    fn solved(spec: &str, state: State) -> Maximizer {
+        let mut maximizer = maximizer(spec, state);
+        maximizer.collect();
+        maximizer
+    }
+
+    /// Solves data.json from the default start, the route as written by --route-out.
+    fn solve(spec: &str) -> serde_json::Value {
+        let maximizer = solved(spec, State::new(TTL_PER_ITERATION));
+
+        let route = route::route_from_json(&route::route_to_json(&maximizer.state, maximizer.algorithm.name())).unwrap();
+        let steps = route::replay(&route, State::new(TTL_PER_ITERATION), &maximizer.nodes,
+                                  &maximizer.relationships, maximizer.value_model.as_ref()).unwrap();
+        assert_eq!(steps.last().unwrap().score, maximizer.state.score, "{} does not replay", spec);
+        route::route_to_json(&maximizer.state, maximizer.algorithm.name())
+    }
+
+    /// Compares with the route in testdata, UPDATE_GOLDEN=1 rewrites it after an intended change.
+    fn check_golden(name: &str, route: serde_json::Value) {
+        let path = format!("testdata/{}.json", name);
+        if std::env::var("UPDATE_GOLDEN").is_ok() {
+            std::fs::write(&path, serde_json::to_string_pretty(&route).unwrap() + "\n").unwrap();
+        }
+        assert_eq!(route, read_json(&path), "{} changed, run with UPDATE_GOLDEN=1 if intended", name);
+    }
+
+    #[test]
+    fn simple_search_golden_route() {
+        check_golden("simple_route", solve("simple"));
+    }
+
+    #[test]
+    fn depth_search_golden_route() {
+        check_golden("depth_route", solve("depth:30"));
+    }
+
+    #[test]
+    fn resumed_route_ends_like_an_uninterrupted_one() {
+        let (nodes, relationships) = load_data();
+        for spec in ["simple", "depth:30"] {
+            let full = solve(spec);
+            let route = route::route_from_json(&full).unwrap();
+
+            // Walk the first hops of the route, save and carry on from there.
+            let mut state = State::new(TTL_PER_ITERATION);
+            let collected = route[0].collected && state.force_collect(&nodes[&route[0].company], &nodes, &value::Additive {});
+            state.record(Action::new(route[0].company.clone(), collected));
+            for action in &route[1..10] {
+                let path = route::find_path(&relationships, &state.current_company, &action.company).unwrap();
+                state.goto(path, &nodes, &value::Additive {}, action.collected);
+            }
+            let saved = route::route_to_json(&state, "partial");
+            let resumed = route::state_from_json(&saved).unwrap();
+            assert_eq!(route::route_to_json(&resumed, "partial"), saved);
+
+            let maximizer = solved(spec, resumed);
+            assert_eq!(route::route_to_json(&maximizer.state, maximizer.algorithm.name()), full, "{} resumed differently", spec);
+        }
+        assert!(route::state_from_json(&serde_json::json!({"steps": [{"company": "Neo4j"}]})).is_err());
+    }
+
+    #[test]
+    fn explanations_follow_the_route() {
+        let mut maximizer = maximizer("depth:30", State::new(TTL_PER_ITERATION));
+        maximizer.explanations = Some(vec![]);
+        maximizer.collect();
+        assert_eq!(route::route_to_json(&maximizer.state, maximizer.algorithm.name()), read_json("testdata/depth_route.json"));
+
+        let decisions = maximizer.explanations.as_ref().unwrap();
+        assert_eq!(decisions.len(), maximizer.state.last_companies.len());
+        assert!(decisions[0].candidates.is_empty());
+        for (decision, action) in decisions.iter().zip(&maximizer.state.last_companies) {
+            assert_eq!((&decision.chosen, decision.collected), (&action.company, action.collected));
+            assert!(!decision.reason().is_empty());
+        }
+        for decision in &decisions[1..] {
+            let best = decision.candidates.iter().map(|c| c.score).fold(f64::MIN, f64::max);
+            let chosen = decision.candidates.iter().find(|c| c.to == decision.chosen).unwrap();
+            assert_eq!(chosen.score, best);
+        }
+        assert!(decisions[1].candidates.iter().any(|c| !c.line.is_empty()));
+        assert_eq!(explain::to_json(decisions).as_array().unwrap().len(), decisions.len());
+    }
+
+    #[test]
+    fn pruning_keeps_the_scores_and_lines_replay() {
+        for seed in 0..8 {
+            let (nodes, relationships) = generator::generate(&generator::GeneratorConfig { nodes: 14, seed, ..Default::default() });
+            let subnets = Subnets::new(&relationships, &nodes);
+            let state = State::starting("Neo4j".to_string(), 600, 3);
+            // The maximizer only holds the graph and value model the search reads.
+            let maximizer = Maximizer::new(state, nodes, relationships, algorithm_from_spec("simple", "Neo4j".to_string()).unwrap(),
+                                           subnets, Box::new(value::Additive {}));
+            let algorithm = DepthSearchAlgo::new(150, "Neo4j".to_string());
+            let bound = SwagBound::new(&maximizer, algorithm.collect_limit);
+            // Nothing is ever worth less than this, so nothing is skipped.
+            let unbounded = SwagBound { companies: vec![], by_swag: vec![(String::new(), i64::MAX / 2)] };
+
+            let mut state = maximizer.state.clone();
+            let mut best = i64::MIN;
+            let (pruned, line) = algorithm.recursive_collector(&maximizer, 450, &mut state, &[], &bound, &mut best);
+            let mut best = i64::MIN;
+            let (full, _) = algorithm.recursive_collector(&maximizer, 450, &mut state, &[], &unbounded, &mut best);
+            assert_eq!(pruned, full, "seed {}", seed);
+
+            for action in line.iter().rev() {
+                let path = route::find_path(&maximizer.relationships, &state.current_company, &action.company).unwrap();
+                state.goto(path, &maximizer.nodes, &value::Additive {}, action.collected);
+                assert_eq!(state.last_companies.last().unwrap(), action);
+            }
+            assert_eq!(state.score, pruned, "seed {}: the line does not reach its score", seed);
+        }
+    }
+
+    #[test]
+    fn reads_algorithm_specs() {
+        assert_eq!(algorithm_from_spec("depth:60:commit=2", "Neo4j".to_string()).unwrap().name(),
+                   algorithm_from_spec("depth", "Neo4j".to_string()).unwrap().name());
+        assert_eq!(algorithm_from_spec("depth:commit=0", "Neo4j".to_string()).err(),
+                   Some("0 is not a valid commit in depth:commit=0".to_string()));
+        assert!(algorithm_from_spec("depth:commit=-1", "Neo4j".to_string()).is_err());
+        assert!(algorithm_from_spec("depth:max_depth=x", "Neo4j".to_string()).is_err());
+        assert!(algorithm_from_spec("depth:speed=1", "Neo4j".to_string()).is_err());
+    }
+
+    #[test]
+    fn commits_only_moves_two_searches_agree_on() {
+        let line = |companies: &[&str]| companies.iter().map(|c| Action::new(c.to_string(), false)).collect::<Vec<_>>();
+        let path = |to: &str| Path { to: to.to_string(), cost: 1 };
+        let mut algorithm = DepthSearchAlgo::new(100, "A".to_string());
+        algorithm.commit = 3;
+        assert!(algorithm.planned_moves(&path("B"), line(&["C", "D", "E"])).is_empty());
+        // Went to C as planned and the plan after it still starts with D, E.
+        assert_eq!(algorithm.planned_moves(&path("C"), line(&["D", "E", "F"])), line(&["D", "E"]));
+        assert_eq!(algorithm.plan, line(&["F"]));
+        assert!(algorithm.planned_moves(&path("G"), line(&["H"])).is_empty());
+        algorithm.commit = 1;
+        assert!(algorithm.planned_moves(&path("H"), line(&["I"])).is_empty());
+    }
+
+    #[test]
+    fn committing_planned_moves_searches_less() {
+        for seed in [0, 1, 2, 3, 5] {
+            let (nodes, relationships) = generator::generate(&generator::GeneratorConfig { nodes: 20, seed, ..Default::default() });
+            let run = |spec: &str| {
+                let subnets = Subnets::new(&relationships, &nodes);
+                let mut maximizer = Maximizer::new(State::starting("Neo4j".to_string(), 800, 4), nodes.clone(), relationships.clone(),
+                                                   algorithm_from_spec(spec, "Neo4j".to_string()).unwrap(), subnets, Box::new(value::Additive {}));
+                maximizer.verbose = false;
+                maximizer.collect();
+                let steps = route::replay(&maximizer.state.last_companies, State::starting("Neo4j".to_string(), 800, 4),
+                                          &nodes, &relationships, &value::Additive {}).unwrap();
+                assert_eq!(steps.last().unwrap().score, maximizer.state.score, "seed {}: {} does not replay", seed, spec);
+                (maximizer.state.score, maximizer.algorithm.nodes_expanded())
+            };
+            let (searched, searched_expanded) = run("depth:max_depth=150");
+            let (committed, committed_expanded) = run("depth:max_depth=150:commit=3");
+            assert!(committed >= searched, "seed {}: {} with commit=3, {} without", seed, committed, searched);
+            assert!(committed_expanded < searched_expanded, "seed {}", seed);
+        }
+    }
+}
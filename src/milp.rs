@@ -0,0 +1,503 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::str::FromStr;
+
+use crate::node::NodeKind;
+use crate::{Action, Node, Relationships, State};
+
+/// How a MILP is written for the solvers.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MilpFormat {
+    Lp, // CPLEX LP, read by CBC, HiGHS and `glpsol --lp`.
+    Mps, // Free MPS, read by CBC, HiGHS and `glpsol --freemps`.
+}
+
+impl FromStr for MilpFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lp" => Ok(MilpFormat::Lp),
+            "mps" => Ok(MilpFormat::Mps),
+            other => Err(format!("Unknown MILP format {}, expected lp or mps", other)),
+        }
+    }
+}
+
+impl MilpFormat {
+    /// Guesses the format from the file extension, LP if unsure.
+    pub fn from_path(path: &str) -> MilpFormat {
+        if path.ends_with(".mps") { MilpFormat::Mps } else { MilpFormat::Lp }
+    }
+}
+
+struct Variable {
+    name: String,
+    integer: bool,
+    upper: Option<i64>,
+}
+
+impl Variable {
+    fn binary(&self) -> bool {
+        self.integer && self.upper == Some(1)
+    }
+}
+
+#[derive(PartialEq)]
+enum Sense {
+    Le,
+    Eq,
+}
+
+struct Constraint {
+    name: String,
+    terms: Vec<(usize, i64)>, // Variable and coefficient.
+    sense: Sense,
+    rhs: i64,
+}
+
+/// The instance as a mixed integer program, to find the optimum with any MILP solver. The walk
+/// goes from the start: integer `x_i_j` for how often the path from company i to j is taken,
+/// binary `y_i` for collecting at i and `e_i` for ending at i. An optimal walk takes a shortest
+/// way from each collection to the next, so no path more often than there are collections,
+/// which bounds `x_i_j`. Every company is left as often as it is entered, except the start and
+/// the end, and collecting needs entering (or starting there). A flow `f_i_j` along the paths
+/// taken from the start brings one unit to every company collected at, which keeps them
+/// connected to the start. Travel and collection times fit in the budget.
+///
+/// It counts plain swag and leaves out opening hours and the walk's `ttl`, so it plays by the
+/// official rules; the routes read back are scored by the verifier under the run's rules.
+pub struct Milp {
+    pub(crate) companies: Vec<String>, // Sorted, company i of the variable names.
+    start: usize,
+    variables: Vec<Variable>,
+    objective: Vec<(usize, i64)>,
+    constraints: Vec<Constraint>,
+    hops: Vec<(usize, usize, usize)>, // From, to and the `x` variable.
+    collect: Vec<Option<usize>>, // The `y` variable of each company which can be collected.
+}
+
+impl Milp {
+    /// The program for walking from where `state` is with its time left, collecting only at
+    /// companies not collected yet.
+    pub fn new(state: &State, nodes: &HashMap<String, Node>, relationships: &Relationships) -> Result<Milp, String> {
+        let mut companies: Vec<String> = nodes.keys().cloned().collect();
+        companies.sort();
+        let index: HashMap<&str, usize> = companies.iter().enumerate().map(|(i, c)| (c.as_str(), i)).collect();
+        let start = *index.get(state.current_company.as_str())
+            .ok_or(format!("Unknown start company {}.", state.current_company))?;
+
+        // The cheapest path between each two companies, negative times counted as 0.
+        let mut cheapest: HashMap<(usize, usize), i64> = HashMap::new();
+        for (from, relationship) in relationships {
+            for path in &relationship.paths {
+                if let (Some(&i), Some(&j)) = (index.get(from.as_str()), index.get(path.to.as_str())) {
+                    if i != j {
+                        let cost = cheapest.entry((i, j)).or_insert(path.cost.max(0));
+                        *cost = (*cost).min(path.cost.max(0));
+                    }
+                }
+            }
+        }
+        let mut edges: Vec<((usize, usize), i64)> = cheapest.into_iter().collect();
+        edges.sort();
+
+        let collectable = companies.iter()
+            .filter(|c| nodes[*c].kind() != NodeKind::PassThrough && !state.has_collected(c)).count();
+        let capacity = collectable.max(1) as i64;
+        let mut variables = vec![];
+        let mut add = |name: String, integer, upper| {
+            variables.push(Variable { name, integer, upper });
+            variables.len() - 1
+        };
+        let hops: Vec<(usize, usize, usize)> = edges.iter()
+            .map(|((i, j), _)| (*i, *j, add(format!("x_{}_{}", i, j), true, Some(capacity)))).collect();
+        let collect: Vec<Option<usize>> = companies.iter().enumerate().map(|(i, company)| {
+            let node = &nodes[company];
+            (node.kind() != NodeKind::PassThrough && !state.has_collected(company)).then(|| add(format!("y_{}", i), true, Some(1)))
+        }).collect();
+        let ends: Vec<usize> = (0..companies.len()).map(|i| add(format!("e_{}", i), true, Some(1))).collect();
+        let flows: Vec<usize> = edges.iter().map(|((i, j), _)| add(format!("f_{}_{}", i, j), false, None)).collect();
+
+        let collectable: Vec<(usize, usize)> = collect.iter().enumerate().filter_map(|(i, y)| Some((i, (*y)?))).collect();
+        let objective = collectable.iter().map(|(i, y)| (*y, nodes[&companies[*i]].value)).collect();
+        let mut constraints = vec![Constraint { name: "end".to_string(), terms: ends.iter().map(|e| (*e, 1)).collect(),
+                                                sense: Sense::Eq, rhs: 1 }];
+        for i in 0..companies.len() {
+            let out = hops.iter().filter(|h| h.0 == i).map(|h| (h.2, 1));
+            let into = hops.iter().filter(|h| h.1 == i).map(|h| (h.2, -1));
+            let mut terms: Vec<(usize, i64)> = out.chain(into).collect();
+            terms.push((ends[i], 1));
+            constraints.push(Constraint { name: format!("balance_{}", i), terms, sense: Sense::Eq, rhs: (i == start) as i64 });
+            if let Some(y) = collect[i] {
+                let mut terms = vec![(y, 1)];
+                terms.extend(hops.iter().filter(|h| h.1 == i).map(|h| (h.2, -1)));
+                constraints.push(Constraint { name: format!("collect_{}", i), terms, sense: Sense::Le, rhs: (i == start) as i64 });
+            }
+        }
+        let mut time: Vec<(usize, i64)> = hops.iter().zip(&edges).map(|(h, (_, cost))| (h.2, *cost)).collect();
+        time.extend(collectable.iter().map(|(i, y)| (*y, nodes[&companies[*i]].cost)));
+        constraints.push(Constraint { name: "time".to_string(), terms: time, sense: Sense::Le, rhs: state.time_left });
+        for (i, y) in collect.iter().enumerate() {
+            // Flow in minus flow out is what is used up here, the start sends one per collection.
+            let into = hops.iter().zip(&flows).filter(|(h, _)| h.1 == i).map(|(_, f)| (*f, 1));
+            let out = hops.iter().zip(&flows).filter(|(h, _)| h.0 == i).map(|(_, f)| (*f, -1));
+            let mut terms: Vec<(usize, i64)> = into.chain(out).collect();
+            if i == start {
+                terms.extend(collectable.iter().filter(|(c, _)| *c != start).map(|(_, y)| (*y, 1)));
+            } else if let Some(y) = y {
+                terms.push((*y, -1));
+            }
+            constraints.push(Constraint { name: format!("flow_{}", i), terms, sense: Sense::Eq, rhs: 0 });
+        }
+        for (h, f) in hops.iter().zip(&flows) {
+            constraints.push(Constraint { name: format!("capacity_{}_{}", h.0, h.1), terms: vec![(*f, 1), (h.2, -capacity)],
+                                          sense: Sense::Le, rhs: 0 });
+        }
+        constraints.retain(|c| !c.terms.is_empty());
+        Ok(Milp { companies, start, variables, objective, constraints, hops, collect })
+    }
+
+    pub fn write(&self, format: MilpFormat) -> String {
+        match format {
+            MilpFormat::Lp => self.to_lp(),
+            MilpFormat::Mps => self.to_mps(),
+        }
+    }
+
+    fn header(&self, comment: &str) -> String {
+        let mut header = format!("{} Swag collected walking from {}. x_i_j: times the path from company i to j is taken,\n\
+                                  {} y_i: collect at i, e_i: the walk ends at i, f_i_j: flow keeping the walk connected.\n",
+                                 comment, self.companies[self.start], comment);
+        for (i, company) in self.companies.iter().enumerate() {
+            let _ = writeln!(header, "{} {} {}", comment, i, company);
+        }
+        header
+    }
+
+    /// CPLEX LP, long sums wrapped over several lines.
+    pub fn to_lp(&self) -> String {
+        let terms = |terms: &[(usize, i64)]| {
+            let mut text = String::new();
+            for (n, (variable, coefficient)) in terms.iter().enumerate() {
+                if n > 0 && n % 10 == 0 {
+                    text.push_str("\n   ");
+                }
+                if *coefficient < 0 {
+                    text.push_str(" -");
+                } else if n > 0 {
+                    text.push_str(" +");
+                }
+                let _ = write!(text, " {} {}", coefficient.abs(), self.variables[*variable].name);
+            }
+            text
+        };
+        let mut lp = self.header("\\");
+        let _ = writeln!(lp, "Maximize\n swag:{}", terms(&self.objective));
+        lp.push_str("Subject To\n");
+        for constraint in &self.constraints {
+            let sense = if constraint.sense == Sense::Eq { "=" } else { "<=" };
+            let _ = writeln!(lp, " {}:{} {} {}", constraint.name, terms(&constraint.terms), sense, constraint.rhs);
+        }
+        lp.push_str("Bounds\n");
+        for variable in self.variables.iter().filter(|v| !v.binary()) {
+            if let Some(upper) = variable.upper {
+                let _ = writeln!(lp, " 0 <= {} <= {}", variable.name, upper);
+            }
+        }
+        lp.push_str("Generals\n");
+        for variable in self.variables.iter().filter(|v| v.integer && !v.binary()) {
+            let _ = writeln!(lp, " {}", variable.name);
+        }
+        lp.push_str("Binaries\n");
+        for variable in self.variables.iter().filter(|v| v.binary()) {
+            let _ = writeln!(lp, " {}", variable.name);
+        }
+        lp.push_str("End\n");
+        lp
+    }
+
+    /// Free MPS. The objective is negated and minimized, as not every solver reads OBJSENSE.
+    pub fn to_mps(&self) -> String {
+        let mut columns: Vec<Vec<(&str, i64)>> = vec![vec![]; self.variables.len()];
+        for (variable, coefficient) in &self.objective {
+            columns[*variable].push(("swag", -coefficient));
+        }
+        for constraint in &self.constraints {
+            for (variable, coefficient) in &constraint.terms {
+                columns[*variable].push((&constraint.name, *coefficient));
+            }
+        }
+        let mut mps = self.header("*");
+        mps.push_str("* The objective is the negated swag.\nNAME swag\nROWS\n N swag\n");
+        for constraint in &self.constraints {
+            let _ = writeln!(mps, " {} {}", if constraint.sense == Sense::Eq { "E" } else { "L" }, constraint.name);
+        }
+        mps.push_str("COLUMNS\n");
+        for integer in [true, false] {
+            if integer {
+                mps.push_str(" MARKER 'MARKER' 'INTORG'\n");
+            }
+            for (variable, column) in self.variables.iter().zip(&columns).filter(|(v, _)| v.integer == integer) {
+                for (row, coefficient) in column {
+                    let _ = writeln!(mps, " {} {} {}", variable.name, row, coefficient);
+                }
+            }
+            if integer {
+                mps.push_str(" MARKER 'MARKER' 'INTEND'\n");
+            }
+        }
+        mps.push_str("RHS\n");
+        for constraint in self.constraints.iter().filter(|c| c.rhs != 0) {
+            let _ = writeln!(mps, " RHS {} {}", constraint.name, constraint.rhs);
+        }
+        mps.push_str("BOUNDS\n");
+        for variable in &self.variables {
+            if let Some(upper) = variable.upper {
+                let _ = writeln!(mps, " UP BND {} {}", variable.name, upper);
+            }
+        }
+        mps.push_str("ENDATA\n");
+        mps
+    }
+
+    /// The variable values of a solution file written by CBC (`-solu`), HiGHS
+    /// (`--solution_file`) or GLPK (`-o`). The first number after a variable's name is its value,
+    /// on the next line if the name ends one, as GLPK wraps long names. Variables not listed
+    /// are 0.
+    pub fn read_solution(&self, text: &str) -> Result<Vec<f64>, String> {
+        if text.to_lowercase().contains("infeasible") {
+            return Err("The solver found no feasible solution.".to_string());
+        }
+        let names: HashMap<&str, usize> = self.variables.iter().enumerate().map(|(i, v)| (v.name.as_str(), i)).collect();
+        let mut values: Vec<Option<f64>> = vec![None; self.variables.len()];
+        let mut pending = None;
+        for line in text.lines() {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            if let Some(variable) = pending.take() {
+                values[variable] = tokens.iter().find_map(|t| t.parse().ok());
+            }
+            for (n, token) in tokens.iter().enumerate() {
+                // Duals and reduced costs follow the values in some files, the first is the value.
+                match names.get(token) {
+                    Some(&variable) if values[variable].is_none() => match tokens[n + 1..].iter().find_map(|t| t.parse().ok()) {
+                        Some(value) => values[variable] = Some(value),
+                        None => pending = Some(variable),
+                    },
+                    _ => {}
+                }
+            }
+        }
+        if values.iter().all(|v| v.is_none()) {
+            return Err("The solution names none of the variables, is it of this instance?".to_string());
+        }
+        Ok(values.into_iter().map(|v| v.unwrap_or(0.0)).collect())
+    }
+
+    /// Swag of the solution by the objective.
+    pub fn objective_value(&self, values: &[f64]) -> f64 {
+        self.objective.iter().map(|(variable, coefficient)| *coefficient as f64 * values[*variable]).sum()
+    }
+
+    /// The constraints the solution breaks and the variables which are not whole numbers or
+    /// out of their bounds.
+    pub fn violations(&self, values: &[f64]) -> Vec<String> {
+        const TOLERANCE: f64 = 1e-6;
+        let mut violations: Vec<String> = self.constraints.iter().filter(|c| {
+            let lhs: f64 = c.terms.iter().map(|(variable, coefficient)| *coefficient as f64 * values[*variable]).sum();
+            match c.sense {
+                Sense::Le => lhs > c.rhs as f64 + TOLERANCE,
+                Sense::Eq => (lhs - c.rhs as f64).abs() > TOLERANCE,
+            }
+        }).map(|c| c.name.clone()).collect();
+        violations.extend(self.variables.iter().zip(values)
+            .filter(|(v, value)| (v.integer && (value.round() - *value).abs() > TOLERANCE) || **value < -TOLERANCE
+                || v.upper.is_some_and(|upper| **value > upper as f64 + TOLERANCE))
+            .map(|(v, _)| v.name.clone()));
+        violations
+    }
+
+    /// The route walking every path the solution takes from the start as often as it takes it,
+    /// collecting where it says on the first visit.
+    pub fn route(&self, values: &[f64]) -> Result<Vec<Action>, String> {
+        let taken = |variable: usize| values[variable] > 0.5;
+        let mut out: Vec<Vec<usize>> = vec![vec![]; self.companies.len()];
+        let mut times: HashMap<(usize, usize), usize> = HashMap::new();
+        for (from, to, x) in &self.hops {
+            let count = values[*x].round().max(0.0) as usize;
+            out[*from].extend(std::iter::repeat_n(*to, count));
+            if count > 0 {
+                times.insert((*from, *to), count);
+            }
+        }
+        // Hierholzer's algorithm, which gives a trail over all the paths if there is one.
+        let mut next = vec![0; self.companies.len()];
+        let mut stack = vec![self.start];
+        let mut trail = vec![];
+        while let Some(&company) = stack.last() {
+            match out[company].get(next[company]) {
+                Some(&to) => {
+                    next[company] += 1;
+                    stack.push(to);
+                }
+                None => trail.push(stack.pop().unwrap()),
+            }
+        }
+        trail.reverse();
+        let mut used: HashMap<(usize, usize), usize> = HashMap::new();
+        for hop in trail.windows(2) {
+            *used.entry((hop[0], hop[1])).or_default() += 1;
+        }
+        if used != times {
+            return Err("The paths the solution takes do not form one walk from the start.".to_string());
+        }
+
+        let mut collected = vec![false; self.companies.len()];
+        let mut route = vec![];
+        for company in trail {
+            let collect = self.collect[company].is_some_and(taken) && !collected[company];
+            collected[company] |= collect;
+            route.push(Action::new(self.companies[company].clone(), collect));
+        }
+        if let Some(company) = (0..self.companies.len()).find(|&i| self.collect[i].is_some_and(taken) && !collected[i]) {
+            return Err(format!("The solution collects at {}, which its walk does not reach.", self.companies[company]));
+        }
+        Ok(route)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::route;
+    use crate::rules::Rules;
+    use crate::value::Additive;
+    use crate::{Path, Relationship};
+
+    fn instance() -> (HashMap<String, Node>, Relationships) {
+        // A - B - Start - C both ways, with BadCorp off C.
+        let nodes: HashMap<String, Node> = [("A", 100), ("B", 30), ("BadCorp", -5), ("C", 50), ("Start", 10)].iter()
+            .map(|(name, value)| (name.to_string(), Node::new(name.to_string(), *value, 10)))
+            .collect();
+        let mut relationships = Relationships::new();
+        for (from, to) in [("A", "B"), ("B", "Start"), ("Start", "C"), ("C", "BadCorp")] {
+            for (a, b) in [(from, to), (to, from)] {
+                relationships.entry(a.to_string()).or_insert(Relationship { paths: vec![] })
+                    .paths.push(Path { to: b.to_string(), cost: 20 });
+            }
+        }
+        (nodes, relationships)
+    }
+
+    fn milp() -> Milp {
+        let (nodes, relationships) = instance();
+        Milp::new(&State::starting("Start".to_string(), 100, 4), &nodes, &relationships).unwrap()
+    }
+
+    // Companies are A 0, B 1, BadCorp 2, C 3, Start 4: walk to A collecting everything on the way.
+    const CBC: &str = "Optimal - objective value 140.00000000
+      0 x_1_0                   1                      0
+      3 x_4_1                   1                      0
+      9 y_0                     1                   -100
+     10 y_1                     1                    -30
+     12 y_4                     1                    -10
+     13 e_0                     1                      0
+     20 f_1_0                   1                      0
+     23 f_4_1                   2                      0
+";
+
+    #[test]
+    fn writes_lp_and_mps() {
+        let milp = milp();
+        let lp = milp.to_lp();
+        assert!(lp.contains("\\ 2 BadCorp\n"));
+        assert!(lp.contains("Maximize\n swag: 100 y_0 + 30 y_1 + 50 y_3 + 10 y_4\n"));
+        assert!(lp.contains(" end: 1 e_0 + 1 e_1 + 1 e_2 + 1 e_3 + 1 e_4 = 1\n"));
+        assert!(lp.contains(" time: 20 x_0_1 + 20 x_1_0 + 20 x_1_4 + 20 x_2_3 + 20 x_3_2 + 20 x_3_4 + 20 x_4_1 + 20 x_4_3 \
+                             + 10 y_0 + 10 y_1\n    + 10 y_3 + 10 y_4 <= 100\n"));
+        assert!(lp.contains(" flow_4: 1 f_1_4 + 1 f_3_4 - 1 f_4_1 - 1 f_4_3 + 1 y_0 + 1 y_1 + 1 y_3 = 0\n"));
+        assert!(lp.contains(" capacity_4_1: 1 f_4_1 - 4 x_4_1 <= 0\n"));
+        assert!(!lp.contains(" y_2"));
+        assert!(lp.contains("Bounds\n 0 <= x_0_1 <= 4\n") && !lp.contains("<= f_"));
+        assert!(lp.contains("Generals\n x_0_1\n") && lp.contains("Binaries\n y_0\n") && lp.ends_with(" e_4\nEnd\n"));
+
+        let mps = milp.to_mps();
+        assert!(mps.contains("ROWS\n N swag\n E end\n"));
+        assert!(mps.contains(" y_0 swag -100\n"));
+        assert!(mps.contains(" RHS time 100\n"));
+        assert!(mps.contains(" UP BND e_4 1\n") && mps.contains(" UP BND x_4_3 4\n"));
+        assert!(mps.ends_with("ENDATA\n"));
+        assert_eq!(MilpFormat::from_path("instance.mps"), MilpFormat::Mps);
+    }
+
+    #[test]
+    fn reads_the_solution_of_each_solver() {
+        let milp = milp();
+        let highs = "Model status\nOptimal\n\n# Primal solution values\nFeasible\nObjective 140\n# Columns 4\n\
+                     x_1_0 1\nx_4_1 1\ny_0 1\ny_1 1\n# Dual solution values\nNone\n# Columns 2\ny_0 -100\ny_1 -30\n";
+        let glpk = "Status:     INTEGER OPTIMAL\n   No. Column name       Activity     Lower bound   Upper bound\n\
+                    ------ ------------    ------------- ------------- -------------\n\
+                         1 x_1_0        *              1             0             1\n\
+                         2 y_0\n                       *              1             0             1\n\
+                         3 y_1          *              0             0             1\n";
+        let highs = milp.read_solution(highs).unwrap();
+        let glpk = milp.read_solution(glpk).unwrap();
+        let value = |values: &[f64], name: &str| values[milp.variables.iter().position(|v| v.name == name).unwrap()];
+        assert_eq!([value(&highs, "x_1_0"), value(&highs, "y_0"), value(&highs, "y_1"), value(&highs, "e_0")], [1.0, 1.0, 1.0, 0.0]);
+        assert_eq!([value(&glpk, "x_1_0"), value(&glpk, "y_0"), value(&glpk, "y_1")], [1.0, 1.0, 0.0]);
+        assert!(milp.read_solution("Infeasible - objective value 0").is_err());
+        assert!(milp.read_solution("nothing here 1 2 3").is_err());
+    }
+
+    #[test]
+    fn solution_comes_back_as_a_route_the_verifier_scores() {
+        let (nodes, relationships) = instance();
+        let milp = milp();
+        let values = milp.read_solution(CBC).unwrap();
+        assert_eq!(milp.violations(&values), Vec::<String>::new());
+        assert_eq!(milp.objective_value(&values), 140.0);
+        let route = milp.route(&values).unwrap();
+        let companies: Vec<String> = route.iter().map(|a| a.to_string()).collect();
+        assert_eq!(companies, ["(Start:Collected)", "(B:Collected)", "(A:Collected)"]);
+        let start = State::starting("Start".to_string(), 100, 4).with_rules(Rules::official());
+        let steps = route::replay(&route, start, &nodes, &relationships, &Additive {}).unwrap();
+        assert_eq!(steps.last().unwrap().score, 140);
+
+        // Collecting at C without the flow reaching it is not a solution.
+        let mut broken = values.clone();
+        broken[milp.collect[3].unwrap()] = 1.0;
+        assert!(milp.violations(&broken).contains(&"collect_3".to_string()));
+        assert!(milp.route(&broken).is_err());
+    }
+
+    #[test]
+    fn takes_a_path_as_often_as_the_walk_needs() {
+        // The one way into M has to be taken twice, to reach each of its dead ends P and Q.
+        let nodes: HashMap<String, Node> = [("H", 0), ("M", 0), ("P", 100), ("Q", 100)].iter()
+            .map(|(name, value)| (name.to_string(), Node::new(name.to_string(), *value, 10)))
+            .collect();
+        let mut relationships = Relationships::new();
+        for (from, to) in [("H", "M"), ("M", "P"), ("M", "Q"), ("P", "H"), ("Q", "H")] {
+            relationships.entry(from.to_string()).or_insert(Relationship { paths: vec![] })
+                .paths.push(Path { to: to.to_string(), cost: 10 });
+        }
+        let milp = Milp::new(&State::starting("H".to_string(), 100, 4), &nodes, &relationships).unwrap();
+        // H 0, M 1, P 2, Q 3.
+        let mut values = vec![0.0; milp.variables.len()];
+        for (name, value) in [("x_0_1", 2.0), ("x_1_2", 1.0), ("x_2_0", 1.0), ("x_1_3", 1.0), ("y_2", 1.0), ("y_3", 1.0),
+                              ("e_3", 1.0), ("f_0_1", 2.0), ("f_1_2", 1.0), ("f_1_3", 1.0)] {
+            values[milp.variables.iter().position(|v| v.name == name).unwrap()] = value;
+        }
+        assert_eq!(milp.violations(&values), Vec::<String>::new());
+        assert_eq!(milp.objective_value(&values), 200.0);
+        let route = milp.route(&values).unwrap();
+        let companies: Vec<String> = route.iter().map(|a| a.to_string()).collect();
+        assert_eq!(companies, ["(H)", "(M)", "(P:Collected)", "(H)", "(M)", "(Q:Collected)"]);
+        let start = State::starting("H".to_string(), 100, 4).with_rules(Rules::official());
+        assert_eq!(route::replay(&route, start, &nodes, &relationships, &Additive {}).unwrap().last().unwrap().score, 200);
+
+        // No path is taken more often than there are collections.
+        values[0] = 3.0;
+        assert!(milp.violations(&values).contains(&"x_0_1".to_string()));
+    }
+}
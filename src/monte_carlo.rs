@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use crate::{Action, Node, Relationships};
+use crate::rng::Rng;
+use crate::value::ValueModel;
+
+/// Outcome of replaying a route many times with sampled collection times.
+pub struct Estimate {
+    pub(crate) runs: usize,
+    pub(crate) expected_score: f64,
+    pub(crate) over_budget_probability: f64,
+    pub(crate) worst_score: i64,
+    pub(crate) best_score: i64,
+}
+
+impl Display for Estimate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Monte Carlo over {} runs: expected score {:.1} (worst {}, best {}), P(over budget) {:.3}",
+               self.runs, self.expected_score, self.worst_score, self.best_score, self.over_budget_probability)
+    }
+}
+
+fn path_cost(relationships: &Relationships, from: &str, to: &str) -> Result<i64, String> {
+    relationships.get(from).iter()
+        .flat_map(|rel| &rel.paths)
+        .filter(|p| p.to == to)
+        .map(|p| p.cost).min()
+        .ok_or_else(|| format!("Route goes from {} to {} but there is no such path.", from, to))
+}
+
+/// Walks the route once, returns the score and whether some planned collection did not finish
+/// within the budget. A hop which can not be finished in time ends the walk.
+fn simulate(route: &[Action], nodes: &HashMap<String, Node>, relationships: &Relationships,
+            model: &dyn ValueModel, budget: i64, rng: &mut Rng) -> Result<(i64, bool), String> {
+    let mut elapsed = 0;
+    let mut score = 0;
+    for (i, action) in route.iter().enumerate() {
+        if i > 0 {
+            let travel = path_cost(relationships, &route[i - 1].company, &action.company)?;
+            if elapsed + travel > budget {
+                break;
+            }
+            elapsed += travel;
+        }
+        if action.collected {
+            let node = nodes.get(&action.company).unwrap();
+            if let Some(start) = node.collection_start(elapsed) {
+                elapsed = start + node.sample_cost(rng);
+                if elapsed > budget {
+                    return Ok((score, true));
+                }
+                score += model.marginal_value(node, &route[..i], nodes);
+            }
+        }
+    }
+    Ok((score, false))
+}
+
+pub fn evaluate(route: &[Action], nodes: &HashMap<String, Node>, relationships: &Relationships,
+                model: &dyn ValueModel, budget: i64, runs: usize, seed: u64) -> Result<Estimate, String> {
+    let mut rng = Rng::new(seed);
+    let mut total = 0;
+    let mut over_budget = 0;
+    let mut worst_score = i64::MAX;
+    let mut best_score = i64::MIN;
+    for _ in 0..runs {
+        let (score, over) = simulate(route, nodes, relationships, model, budget, &mut rng)?;
+        total += score;
+        worst_score = worst_score.min(score);
+        best_score = best_score.max(score);
+        if over {
+            over_budget += 1;
+        }
+    }
+    Ok(Estimate {
+        runs,
+        expected_score: total as f64 / runs.max(1) as f64,
+        over_budget_probability: over_budget as f64 / runs.max(1) as f64,
+        worst_score,
+        best_score,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::Additive;
+    use crate::{Path, Relationship};
+
+    fn instance(queue: &str) -> (HashMap<String, Node>, Relationships, Vec<Action>) {
+        let nodes: HashMap<String, Node> = [
+            Node::new("Neo4j".to_string(), 0, 0),
+            Node::new("Queue".to_string(), 100, 30).with_distribution(queue.parse().unwrap()),
+        ].into_iter().map(|n| (n.name.clone(), n)).collect();
+        let mut relationships = Relationships::new();
+        relationships.insert("Neo4j".to_string(), Relationship { paths: vec![Path { to: "Queue".to_string(), cost: 50 }] });
+        let route = vec![Action::new("Neo4j".to_string(), false), Action::new("Queue".to_string(), true)];
+        (nodes, relationships, route)
+    }
+
+    #[test]
+    fn same_seed_same_estimate() {
+        let (nodes, relationships, route) = instance("10,30,90");
+        let estimate = |seed| evaluate(&route, &nodes, &relationships, &Additive {}, 100, 500, seed).unwrap();
+        let (a, b) = (estimate(3), estimate(3));
+        assert_eq!((a.expected_score, a.over_budget_probability), (b.expected_score, b.over_budget_probability));
+        // 50 travel leaves 50, queues over 50 take the swag away.
+        assert!(a.over_budget_probability > 0.0 && a.over_budget_probability < 1.0);
+        assert_eq!((a.worst_score, a.best_score), (0, 100));
+        assert!((a.expected_score - 100.0 * (1.0 - a.over_budget_probability)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn flags_every_run_over_budget() {
+        let (nodes, relationships, route) = instance("60,70,80");
+        let estimate = evaluate(&route, &nodes, &relationships, &Additive {}, 100, 50, 1).unwrap();
+        assert_eq!((estimate.over_budget_probability, estimate.expected_score), (1.0, 0.0));
+        let estimate = evaluate(&route, &nodes, &relationships, &Additive {}, 200, 50, 1).unwrap();
+        assert_eq!((estimate.over_budget_probability, estimate.expected_score), (0.0, 100.0));
+    }
+
+    #[test]
+    fn a_route_over_a_missing_path_is_an_error() {
+        let (nodes, relationships, mut route) = instance("10,30,90");
+        route.push(Action::new("Neo4j".to_string(), false));
+        let error = evaluate(&route, &nodes, &relationships, &Additive {}, 200, 10, 1).err();
+        assert_eq!(error.as_deref(), Some("Route goes from Queue to Neo4j but there is no such path."));
+    }
+}
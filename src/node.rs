@@ -1,36 +1,160 @@
 use std::fmt::{Debug, Display, Formatter};
 
+use crate::distribution::CostDistribution;
+use crate::rng::Rng;
+
+/// Interval of elapsed time in which collection at a company may start.
+#[derive(Hash, PartialEq, Eq, Clone, Copy)]
+pub struct TimeWindow {
+    pub(crate) earliest: i64,
+    pub(crate) latest: i64,
+}
+
+impl TimeWindow {
+    pub fn new(earliest: i64, latest: i64) -> TimeWindow {
+        TimeWindow{earliest, latest}
+    }
+}
+
+impl Display for TimeWindow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}, {}]", self.earliest, self.latest)
+    }
+}
+
+/// Swag per time, defined for zero cost too: free swag is infinitely worth it, free nothing is
+/// worth nothing and free negative swag is infinitely bad.
+pub fn ratio(value: i64, cost: i64) -> f64 {
+    if cost == 0 {
+        match value.signum() {
+            1 => f64::INFINITY,
+            0 => 0.0,
+            _ => f64::NEG_INFINITY,
+        }
+    } else {
+        (value as f64)/(cost as f64)
+    }
+}
+
+/// How a company may be used by a route.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum NodeKind {
+    Collectible,
+    Free, // Positive swag for no time, always collect.
+    PassThrough, // No or negative swag, never collect, only walk through.
+}
+
 #[derive(Hash,PartialEq,Eq)]
 pub struct Node {
     pub(crate) name: String,
     pub(crate) value: i64,
     pub(crate) cost: i64,
+    pub(crate) window: Option<TimeWindow>,
+    pub(crate) distribution: Option<CostDistribution>, // Uncertain collection time, `cost` is the nominal one.
+    pub(crate) category: Option<String>, // Kind of swag handed out, e.g. "t-shirt".
 }
 
 impl Display for Node {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Name {} value {} cost {}", self.name, self.value, self.cost)
+        write!(f, "Name {} value {} cost {}", self.name, self.value, self.cost)?;
+        if let Some(window) = &self.window {
+            write!(f, " open {}", window)?;
+        }
+        if let Some(distribution) = &self.distribution {
+            write!(f, " cost {}", distribution)?;
+        }
+        if let Some(category) = &self.category {
+            write!(f, " category {}", category)?;
+        }
+        Ok(())
     }
 }
 
 impl Debug for Node {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Name {} value {} cost {}", self.name, self.value, self.cost)
+        Display::fmt(self, f)
     }
 }
 
 impl Node {
     pub fn new(name: String, value: i64, cost: i64) -> Node {
-        Node{name, value, cost}
+        Node{name, value, cost, window: None, distribution: None, category: None}
+    }
+
+    pub fn with_window(mut self, window: TimeWindow) -> Node {
+        self.window = Some(window);
+        self
+    }
+
+    pub fn with_distribution(mut self, distribution: CostDistribution) -> Node {
+        self.distribution = Some(distribution);
+        self
+    }
+
+    /// Draws a collection time, nodes without a distribution always take `cost`.
+    pub fn sample_cost(&self, rng: &mut Rng) -> i64 {
+        self.distribution.as_ref().map_or(self.cost, |d| d.sample(rng))
     }
 
     pub fn value_per_cost(&self) -> f64 {
-        (self.value as f64)/(self.cost as f64)
+        ratio(self.value, self.cost)
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        if self.value <= 0 {
+            NodeKind::PassThrough
+        } else if self.cost == 0 {
+            NodeKind::Free
+        } else {
+            NodeKind::Collectible
+        }
+    }
+
+    /// Time at which collection can start when arriving at `arrival`, waiting for the booth to
+    /// open if needed. None if the booth has already closed.
+    pub fn collection_start(&self, arrival: i64) -> Option<i64> {
+        match &self.window {
+            None => Some(arrival),
+            Some(window) if arrival > window.latest => None,
+            Some(window) => Some(arrival.max(window.earliest)),
+        }
     }
 }
 
 impl Clone for Node {
     fn clone(&self) -> Self {
-        Node::new(self.name.clone(), self.value, self.cost)
+        Node{ window: self.window, distribution: self.distribution.clone(), category: self.category.clone(),
+            ..Node::new(self.name.clone(), self.value, self.cost) }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_cost_ratios_are_well_defined() {
+        assert_eq!(Node::new("free".to_string(), 10, 0).value_per_cost(), f64::INFINITY);
+        assert_eq!(Node::new("nothing".to_string(), 0, 0).value_per_cost(), 0.0);
+        assert_eq!(Node::new("bad".to_string(), -1, 0).value_per_cost(), f64::NEG_INFINITY);
+        assert!(!Node::new("bad".to_string(), -1, 95).value_per_cost().is_nan());
+    }
+
+    #[test]
+    fn classifies_nodes() {
+        assert_eq!(Node::new("BadCorp".to_string(), -1, 95).kind(), NodeKind::PassThrough);
+        assert_eq!(Node::new("Empty".to_string(), 0, 10).kind(), NodeKind::PassThrough);
+        assert_eq!(Node::new("Free".to_string(), 5, 0).kind(), NodeKind::Free);
+        assert_eq!(Node::new("Neo4j".to_string(), 350, 147).kind(), NodeKind::Collectible);
+    }
+
+    #[test]
+    fn collection_starts_within_the_window() {
+        let booth = Node::new("Booth".to_string(), 100, 20).with_window(TimeWindow::new(100, 200));
+        assert_eq!(booth.collection_start(40), Some(100)); // Early, wait for the opening.
+        assert_eq!(booth.collection_start(150), Some(150));
+        assert_eq!(booth.collection_start(200), Some(200)); // The last moment still counts.
+        assert_eq!(booth.collection_start(201), None);
+        assert_eq!(Node::new("Always".to_string(), 100, 20).collection_start(9999), Some(9999));
+    }
+}
@@ -0,0 +1,178 @@
+use std::io::{BufRead, Write};
+
+use serde_json::json;
+
+use crate::Maximizer;
+use crate::route::route_to_json;
+
+/// What the user reports back after a hop.
+struct Observation {
+    company: Option<String>, // Where we ended up, if not where we were told to go.
+    time_spent: i64,
+    collected: bool,
+}
+
+/// Reads either a JSON line `{"time_spent": 120, "collected": true, "at": "Neo4j"}` or the
+/// shorthand `120 y [company]`. None if the line can not be understood.
+fn parse_observation(line: &str) -> Option<Observation> {
+    let line = line.trim();
+    if line.starts_with('{') {
+        let json: serde_json::Value = serde_json::from_str(line).ok()?;
+        Some(Observation {
+            company: json.get("at").and_then(|a| a.as_str()).map(|a| a.to_string()),
+            time_spent: json.get("time_spent")?.as_i64()?,
+            collected: json.get("collected").and_then(|c| c.as_bool()).unwrap_or(false),
+        })
+    } else {
+        let mut parts = line.split_whitespace();
+        let time_spent = parts.next()?.parse().ok()?;
+        let collected = matches!(parts.next(), Some("y" | "yes" | "true"));
+        let company = parts.next().map(|c| c.to_string());
+        Some(Observation { company, time_spent, collected })
+    }
+}
+
+/// Plans one hop at a time: suggests the next move as a JSON line on `output`, waits for the
+/// observed outcome on `input`, updates the state with it and plans again from where we are.
+/// Stops when the time is up or the input ends. After every hop the route so far is saved to
+/// `state_out` if given, to resume from after a restart.
+pub fn run(maximizer: &mut Maximizer, input: impl BufRead, mut output: impl Write,
+           state_out: Option<&str>) -> std::io::Result<()> {
+    maximizer.verbose = false;
+    let mut lines = input.lines();
+    let mut step = maximizer.state.last_companies.len();
+
+    while maximizer.state.time_left > 0 {
+        // The first suggestion is whether to collect where we start.
+        let (to, travel) = if maximizer.state.last_companies.is_empty() {
+            (maximizer.state.current_company.clone(), 0)
+        } else {
+            let path = maximizer.max_score_collect().expect("No valid path exists.");
+            (path.to.clone(), path.cost)
+        };
+        let node = maximizer.nodes.get(&to).unwrap();
+        writeln!(output, "{}", json!({
+            "step": step,
+            "goto": to,
+            "travel": travel,
+            "collect": maximizer.collect_here(&to),
+            "expected_cost": node.cost,
+            "score": maximizer.state.score,
+            "time_left": maximizer.state.time_left,
+        }))?;
+        output.flush()?;
+
+        let (company, observation) = loop {
+            let line = match lines.next() {
+                None => return Ok(()),
+                Some(line) => line?,
+            };
+            match parse_observation(&line) {
+                Some(observation) => {
+                    let company = observation.company.clone().unwrap_or_else(|| to.clone());
+                    let current = &maximizer.state.current_company;
+                    let next_to_current = company == *current || maximizer.relationships.get(current)
+                        .is_some_and(|rel| rel.paths.iter().any(|p| p.to == company));
+                    if !maximizer.nodes.contains_key(&company) {
+                        writeln!(output, "{}", json!({"error": format!("unknown company {}", company)}))?;
+                    } else if !next_to_current {
+                        writeln!(output, "{}", json!({"error": format!("no path from {} to {}", current, company)}))?;
+                    } else {
+                        break (company, observation);
+                    }
+                }
+                None => writeln!(output, "{}", json!({"error": "expected {\"time_spent\": n, \"collected\": bool}"}))?,
+            }
+        };
+
+        maximizer.state.observe(&company, observation.time_spent, observation.collected,
+                                &maximizer.nodes, maximizer.value_model.as_ref());
+        maximizer.algorithm.set_current_position(company);
+        if let Some(path) = state_out {
+            let route = route_to_json(&maximizer.state, maximizer.algorithm.name());
+            std::fs::write(path, serde_json::to_string_pretty(&route)?)?;
+        }
+        step += 1;
+    }
+    writeln!(output, "{}", json!({"done": true, "score": maximizer.state.score}))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::value::Additive;
+    use crate::{algorithm_from_spec, Node, Path, Relationship, Relationships, State, Subnets};
+
+    #[test]
+    fn parses_json_and_shorthand_observations() {
+        let json = parse_observation(r#"{"time_spent": 120, "collected": true, "at": "Neo4j"}"#).unwrap();
+        assert_eq!((json.company.as_deref(), json.time_spent, json.collected), (Some("Neo4j"), 120, true));
+        let short = parse_observation(" 45 n ").unwrap();
+        assert_eq!((short.company, short.time_spent, short.collected), (None, 45, false));
+        let moved = parse_observation("120 y Arkad").unwrap();
+        assert_eq!((moved.company.as_deref(), moved.collected), (Some("Arkad"), true));
+        assert!(parse_observation("soon").is_none());
+        assert!(parse_observation(r#"{"collected": true}"#).is_none());
+    }
+
+    fn maximizer() -> Maximizer {
+        let nodes: HashMap<String, Node> = [("Neo4j", 0, 0), ("A", 100, 20), ("B", 50, 10)].iter()
+            .map(|(name, value, cost)| (name.to_string(), Node::new(name.to_string(), *value, *cost)))
+            .collect();
+        let mut relationships = Relationships::new();
+        for (from, others) in [("Neo4j", ["A", "B"]), ("A", ["Neo4j", "B"]), ("B", ["Neo4j", "A"])] {
+            let paths = others.iter().map(|to| Path { to: to.to_string(), cost: 10 }).collect();
+            relationships.insert(from.to_string(), Relationship { paths });
+        }
+        let subnets = Subnets::new(&relationships, &nodes);
+        Maximizer::new(State::starting("Neo4j".to_string(), 200, 4), nodes, relationships,
+                       algorithm_from_spec("greedy", "Neo4j".to_string()).unwrap(), subnets, Box::new(Additive {}))
+    }
+
+    #[test]
+    fn drives_the_walk_from_a_script() {
+        let mut maximizer = maximizer();
+        let script = "{\"time_spent\": 0, \"collected\": false}\nnonsense\n30 y A\n5 y A\n12 y Nowhere\n";
+        let mut output = vec![];
+        run(&mut maximizer, Cursor::new(script), &mut output, None).unwrap();
+
+        let lines: Vec<serde_json::Value> = String::from_utf8(output).unwrap().lines()
+            .map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!((lines[0]["step"].as_i64(), lines[0]["goto"].as_str()), (Some(0), Some("Neo4j")));
+        assert_eq!(lines[1]["step"], 1);
+        assert!(lines[2]["error"].is_string());
+        assert_eq!(lines[3]["step"], 2);
+        assert_eq!((lines[3]["score"].as_i64(), lines[3]["time_left"].as_i64()), (Some(100), Some(170)));
+        assert_eq!(lines[4]["step"], 3);
+        assert!(lines[5]["error"].as_str().unwrap().contains("Nowhere"));
+        assert_eq!(lines.len(), 6);
+        // The input ended before the time was up.
+        assert!(lines.iter().all(|l| l.get("done").is_none()));
+
+        // Reporting the swag at A again does not count it twice.
+        let state = &maximizer.state;
+        assert_eq!((state.score, state.time_left, state.current_company.as_str()), (100, 165, "A"));
+        let route: Vec<String> = state.last_companies.iter().map(|a| a.to_string()).collect();
+        assert_eq!(route, ["(Neo4j)", "(A:Collected)", "(A)"]);
+    }
+
+    #[test]
+    fn moves_without_a_path_are_an_error() {
+        let mut maximizer = maximizer();
+        maximizer.relationships.get_mut("A").unwrap().paths.retain(|p| p.to != "B");
+        let script = "0 n\n10 y A\n10 y B\n10 n Neo4j\n";
+        let mut output = vec![];
+        run(&mut maximizer, Cursor::new(script), &mut output, None).unwrap();
+
+        let lines: Vec<serde_json::Value> = String::from_utf8(output).unwrap().lines()
+            .map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines[3]["error"], "no path from A to B");
+        assert_eq!(lines[4]["step"], 3);
+        let route: Vec<String> = maximizer.state.last_companies.iter().map(|a| a.to_string()).collect();
+        assert_eq!(route, ["(Neo4j)", "(A:Collected)", "(Neo4j)"]);
+    }
+}
@@ -16,7 +16,7 @@ pub struct Path{
 impl Path {
     pub fn value_per_cost(&self, nodes: &HashMap<String, Node>) -> f64 {
         let temp_node = nodes.get(&self.to).unwrap();
-        (temp_node.value as f64) / ((temp_node.cost + self.cost) as f64)
+        node::ratio(temp_node.value, temp_node.cost + self.cost)
     }
 
 }
@@ -0,0 +1,316 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+
+use crate::graphs::{shortest_paths, shortest_paths_through, travel_times, way_to};
+use crate::node::{self, NodeKind};
+use crate::route;
+use crate::rules::Revisits;
+use crate::value::ValueModel;
+use crate::{Action, Node, Relationships, State};
+
+/// Routes replayed at most while polishing one, which bounds the time on large graphs.
+const MAX_EVALUATIONS: usize = 20_000;
+/// Insertions tried per round, the ones worth the most per added time first.
+const INSERTIONS_TRIED: usize = 20;
+
+/// A route after polishing, with what it scored before.
+pub struct Polished {
+    pub(crate) route: Vec<Action>,
+    pub(crate) before: i64,
+    pub(crate) score: i64,
+    pub(crate) moves: usize, // Reorderings which improved the route.
+    pub(crate) inserted: usize, // Companies collected on top.
+}
+
+/// A change of the visiting order, positions into the tour after the start.
+#[derive(Clone, Copy, Debug)]
+enum Move {
+    Reverse(usize, usize), // 2-opt, the companies from the first to the second in reverse.
+    Shift { from: usize, len: usize, to: usize }, // Or-opt, a run of up to three moved elsewhere.
+    Swap(usize, usize),
+}
+
+/// How good a replayed route is, more score first, then more time left.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+struct Quality {
+    score: i64,
+    time_left: i64,
+}
+
+/// Treats the collected companies of a route as a tour over the shortest travel times between
+/// them and improves the visiting order with 2-opt, Or-opt and swap moves, then spends the time
+/// saved on collecting at more companies. Between two collections the shortest way is walked,
+/// through companies not visited yet where the rules count revisits. Every candidate is replayed
+/// by the rules of `start`, so the polished route is valid and never scores less.
+pub fn polish(route: &[Action], start: &State, nodes: &HashMap<String, Node>,
+              relationships: &Relationships, model: &dyn ValueModel) -> Result<Polished, String> {
+    let before = quality(&route::replay(route, start.clone(), nodes, relationships, model)?);
+    let first = &route[0];
+    let mut tour = vec![first.company.clone()];
+    tour.extend(route[1..].iter().filter(|a| a.collected && !(first.collected && a.company == first.company)).map(|a| a.company.clone()));
+    let polisher = Polisher {
+        start, nodes, relationships, model,
+        start_collected: first.collected,
+        closure: RefCell::new(HashMap::new()),
+        evaluations: Cell::new(0),
+    };
+    let mut best = polisher.evaluate(&tour).unwrap_or(Quality { score: i64::MIN, time_left: 0 });
+    let (mut moves, mut inserted) = (0, 0);
+    loop {
+        moves += polisher.improve(&mut tour, &mut best);
+        if !polisher.insert(&mut tour, &mut best) {
+            break;
+        }
+        inserted += 1;
+    }
+    if best <= before {
+        return Ok(Polished { route: route.to_vec(), before: before.score, score: before.score, moves: 0, inserted: 0 });
+    }
+    Ok(Polished { route: polisher.expand(&tour).unwrap(), before: before.score, score: best.score, moves, inserted })
+}
+
+fn quality(steps: &[route::Step]) -> Quality {
+    let last = steps.last().unwrap();
+    Quality { score: last.score, time_left: last.time_left }
+}
+
+struct Polisher<'a> {
+    start: &'a State,
+    nodes: &'a HashMap<String, Node>,
+    relationships: &'a Relationships,
+    model: &'a dyn ValueModel,
+    start_collected: bool,
+    closure: RefCell<HashMap<String, HashMap<String, i64>>>, // Travel times from the companies asked for.
+    evaluations: Cell<usize>,
+}
+
+impl Polisher<'_> {
+    fn distance(&self, from: &str, to: &str) -> Option<i64> {
+        let mut closure = self.closure.borrow_mut();
+        let times = closure.entry(from.to_string()).or_insert_with(|| travel_times(self.relationships, from));
+        times.get(to).copied()
+    }
+
+    /// Travel along the tour over the metric closure.
+    fn length(&self, tour: &[String]) -> Option<i64> {
+        tour.windows(2).map(|leg| self.distance(&leg[0], &leg[1])).sum()
+    }
+
+    /// The route walking the tour, collecting at every company of it.
+    fn expand(&self, tour: &[String]) -> Option<Vec<Action>> {
+        let mut route = vec![Action::new(tour[0].clone(), self.start_collected)];
+        let mut visited: HashSet<String> = self.start.last_companies.iter().map(|a| a.company.clone()).collect();
+        visited.insert(tour[0].clone());
+        for (from, to) in tour.iter().zip(&tour[1..]) {
+            let way = if self.start.rules.revisits == Revisits::Ttl {
+                way_to(&shortest_paths_through(self.relationships, from, |c| c == to || !visited.contains(c)), to)
+            } else {
+                None
+            };
+            let way = way.or_else(|| way_to(&shortest_paths(self.relationships, from), to))?;
+            for company in way {
+                route.push(Action::new(company.clone(), company == *to));
+                visited.insert(company);
+            }
+        }
+        Some(route)
+    }
+
+    /// The replayed tour, None if it is not a valid route or the evaluations are used up.
+    fn evaluate(&self, tour: &[String]) -> Option<Quality> {
+        if self.evaluations.get() >= MAX_EVALUATIONS {
+            return None;
+        }
+        self.evaluations.set(self.evaluations.get() + 1);
+        let route = self.expand(tour)?;
+        let steps = route::replay(&route, self.start.clone(), self.nodes, self.relationships, self.model).ok()?;
+        Some(quality(&steps))
+    }
+
+    /// Makes the first move found which shortens the travel and improves the replayed route,
+    /// until there is none, returns the number of moves made.
+    fn improve(&self, tour: &mut Vec<String>, best: &mut Quality) -> usize {
+        let mut made = 0;
+        'search: loop {
+            let Some(length) = self.length(tour) else { return made };
+            for candidate in moves(tour.len()) {
+                let changed = applied(tour, candidate);
+                if self.length(&changed).is_none_or(|l| l >= length) {
+                    continue;
+                }
+                if let Some(quality) = self.evaluate(&changed) {
+                    if quality > *best {
+                        *tour = changed;
+                        *best = quality;
+                        made += 1;
+                        continue 'search;
+                    }
+                }
+            }
+            return made;
+        }
+    }
+
+    /// Adds the company collected at the cheapest detour for what it is worth after the
+    /// collections of the tour which improves the replayed route, returns whether one was found.
+    fn insert(&self, tour: &mut Vec<String>, best: &mut Quality) -> bool {
+        let history: Vec<Action> = self.start.last_companies.iter().cloned()
+            .chain(self.expand(tour).unwrap_or_default()).collect();
+        let mut names: Vec<&String> = self.nodes.keys()
+            .filter(|n| self.nodes[*n].kind() != NodeKind::PassThrough && !tour.contains(n) && !self.start.has_collected(n))
+            .collect();
+        names.sort();
+        let mut candidates = vec![];
+        for name in names {
+            let node = &self.nodes[name];
+            let value = self.model.marginal_value(node, &history, self.nodes);
+            for position in 1..=tour.len() {
+                let previous = &tour[position - 1];
+                let detour = match tour.get(position) {
+                    Some(next) => self.distance(previous, name).zip(self.distance(name, next))
+                        .zip(self.distance(previous, next)).map(|((a, b), c)| a + b - c),
+                    None => self.distance(previous, name),
+                };
+                if let Some(added) = detour.map(|d| d + node.cost).filter(|&a| a <= best.time_left) {
+                    candidates.push((node::ratio(value, added), name, position));
+                }
+            }
+        }
+        candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+        for (_, name, position) in candidates.into_iter().take(INSERTIONS_TRIED) {
+            let mut changed = tour.clone();
+            changed.insert(position, name.clone());
+            if let Some(quality) = self.evaluate(&changed) {
+                if quality > *best {
+                    *tour = changed;
+                    *best = quality;
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+/// Every 2-opt, Or-opt and swap move of a tour of `len` companies, keeping the start first.
+fn moves(len: usize) -> Vec<Move> {
+    let mut moves = vec![];
+    for i in 1..len {
+        for j in i + 1..len {
+            moves.push(Move::Reverse(i, j));
+        }
+    }
+    for run in 1..=3 {
+        for from in 1..len.saturating_sub(run - 1) {
+            for to in 1..=len - run {
+                if to != from {
+                    moves.push(Move::Shift { from, len: run, to });
+                }
+            }
+        }
+    }
+    for i in 1..len {
+        for j in i + 1..len {
+            moves.push(Move::Swap(i, j));
+        }
+    }
+    moves
+}
+
+fn applied(tour: &[String], candidate: Move) -> Vec<String> {
+    let mut changed = tour.to_vec();
+    match candidate {
+        Move::Reverse(i, j) => changed[i..=j].reverse(),
+        Move::Shift { from, len, to } => {
+            let run: Vec<String> = changed.drain(from..from + len).collect();
+            changed.splice(to..to, run);
+        }
+        Move::Swap(i, j) => changed.swap(i, j),
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::{generate, GeneratorConfig};
+    use crate::value::{Additive, DiminishingReturns};
+    use crate::{algorithm_from_spec, Maximizer, Path, Relationship, Subnets};
+
+    fn line() -> (HashMap<String, Node>, Relationships) {
+        // Start in the middle of A - B - Start - C, with D off A.
+        let nodes: HashMap<String, Node> = [("Start", 0), ("A", 100), ("B", 100), ("C", 100), ("D", 100)].iter()
+            .map(|(name, value)| (name.to_string(), Node::new(name.to_string(), *value, 10)))
+            .collect();
+        let mut relationships = Relationships::new();
+        for (from, to) in [("A", "B"), ("B", "Start"), ("Start", "C"), ("A", "D")] {
+            for (a, b) in [(from, to), (to, from)] {
+                relationships.entry(a.to_string()).or_insert(Relationship { paths: vec![] })
+                    .paths.push(Path { to: b.to_string(), cost: 20 });
+            }
+        }
+        (nodes, relationships)
+    }
+
+    #[test]
+    fn reorders_the_tour_and_collects_with_the_time_saved() {
+        let (nodes, relationships) = line();
+        // B, back for C and back past B to A: 120 travel and 30 collecting, nothing left for D.
+        let route: Vec<Action> = [("Start", false), ("B", true), ("Start", false), ("C", true), ("Start", false),
+                                  ("B", false), ("A", true)].iter()
+            .map(|(c, collected)| Action::new(c.to_string(), *collected)).collect();
+        let start = State::starting("Start".to_string(), 150, 4).with_rules(crate::rules::Rules::official());
+        let polished = polish(&route, &start, &nodes, &relationships, &Additive {}).unwrap();
+        assert_eq!(polished.before, 300);
+        // C first saves 40, enough for D after A.
+        let companies: Vec<String> = polished.route.iter().map(|a| a.to_string()).collect();
+        assert_eq!(companies, ["(Start)", "(C:Collected)", "(Start)", "(B:Collected)", "(A:Collected)", "(D:Collected)"]);
+        assert_eq!((polished.score, polished.inserted), (400, 1));
+        assert!(polished.moves >= 1);
+    }
+
+    #[test]
+    fn never_scores_less_and_replays() {
+        for seed in 1..4 {
+            let (nodes, relationships) = generate(&GeneratorConfig { nodes: 40, seed, ..GeneratorConfig::default() });
+            let subnets = Subnets::new(&relationships, &nodes);
+            let start = State::starting(GeneratorConfig::default().start, 600, 4);
+            let mut maximizer = Maximizer::new(start.clone(), nodes.clone(), relationships.clone(),
+                                               algorithm_from_spec("simple", start.current_company.clone()).unwrap(),
+                                               subnets, Box::new(Additive {}));
+            maximizer.verbose = false;
+            maximizer.collect();
+            let polished = polish(&maximizer.state.last_companies, &start, &nodes, &relationships, &Additive {}).unwrap();
+            assert_eq!(polished.before, maximizer.state.score);
+            assert!(polished.score >= polished.before);
+            let steps = route::replay(&polished.route, start, &nodes, &relationships, &Additive {}).unwrap();
+            assert_eq!(steps.last().unwrap().score, polished.score);
+        }
+    }
+
+    #[test]
+    fn inserts_what_is_worth_most_after_the_tour() {
+        // A star around Start, the route collects one shirt at S and there is time for one more.
+        let mut nodes: HashMap<String, Node> = [("Start", 0), ("S", 100), ("X", 100), ("Y", 80)].iter()
+            .map(|(name, value)| (name.to_string(), Node::new(name.to_string(), *value, 10)))
+            .collect();
+        for shirt in ["S", "X"] {
+            nodes.get_mut(shirt).unwrap().category = Some("shirt".to_string());
+        }
+        let mut relationships = Relationships::new();
+        for spoke in ["S", "X", "Y"] {
+            for (a, b) in [("Start", spoke), (spoke, "Start")] {
+                relationships.entry(a.to_string()).or_insert(Relationship { paths: vec![] })
+                    .paths.push(Path { to: b.to_string(), cost: 20 });
+            }
+        }
+        let route = vec![Action::new("Start".to_string(), false), Action::new("S".to_string(), true)];
+        let start = State::starting("Start".to_string(), 90, 4).with_rules(crate::rules::Rules::official());
+        // The second shirt is worth 50, less per time than Y.
+        let polished = polish(&route, &start, &nodes, &relationships, &DiminishingReturns { factor: 0.5 }).unwrap();
+        let mut collected: Vec<&str> = polished.route.iter().filter(|a| a.collected).map(|a| a.company.as_str()).collect();
+        collected.sort();
+        assert_eq!(collected, ["S", "Y"]);
+        assert_eq!((polished.before, polished.score, polished.inserted), (100, 180, 1));
+    }
+}
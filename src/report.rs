@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::{Node, Relationships};
+use crate::route::Step;
+use crate::rng::Rng;
+
+const WIDTH: f64 = 1000.0;
+const HEIGHT: f64 = 700.0;
+const TIMELINE_HEIGHT: f64 = 260.0;
+const MARGIN: f64 = 40.0;
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Fruchterman-Reingold spring layout, deterministic for a given seed. Positions are scaled to
+/// fit the drawing area.
+fn layout(names: &[&String], relationships: &Relationships, seed: u64) -> Vec<(f64, f64)> {
+    let index: HashMap<&String, usize> = names.iter().enumerate().map(|(i, n)| (*n, i)).collect();
+    let mut edges = vec![];
+    for (from, rel) in relationships {
+        for path in &rel.paths {
+            if let (Some(&a), Some(&b)) = (index.get(from), index.get(&path.to)) {
+                if a != b {
+                    edges.push((a, b));
+                }
+            }
+        }
+    }
+    edges.sort();
+
+    let mut rng = Rng::new(seed);
+    let mut pos: Vec<(f64, f64)> = names.iter().map(|_| (rng.next_f64() * WIDTH, rng.next_f64() * HEIGHT)).collect();
+    let k = (WIDTH * HEIGHT / names.len().max(1) as f64).sqrt();
+    let iterations = 300;
+    for iteration in 0..iterations {
+        let mut moves = vec![(0.0, 0.0); pos.len()];
+        for i in 0..pos.len() {
+            for j in 0..pos.len() {
+                if i != j {
+                    let (dx, dy) = (pos[i].0 - pos[j].0, pos[i].1 - pos[j].1);
+                    let d = (dx * dx + dy * dy).sqrt().max(0.01);
+                    let force = k * k / d;
+                    moves[i].0 += dx / d * force;
+                    moves[i].1 += dy / d * force;
+                }
+            }
+        }
+        for &(a, b) in &edges {
+            let (dx, dy) = (pos[a].0 - pos[b].0, pos[a].1 - pos[b].1);
+            let d = (dx * dx + dy * dy).sqrt().max(0.01);
+            let force = d * d / k;
+            moves[a].0 -= dx / d * force;
+            moves[a].1 -= dy / d * force;
+            moves[b].0 += dx / d * force;
+            moves[b].1 += dy / d * force;
+        }
+        let temperature = WIDTH / 10.0 * (1.0 - iteration as f64 / iterations as f64);
+        for (p, m) in pos.iter_mut().zip(&moves) {
+            let length = (m.0 * m.0 + m.1 * m.1).sqrt().max(0.01);
+            p.0 += m.0 / length * length.min(temperature);
+            p.1 += m.1 / length * length.min(temperature);
+        }
+    }
+
+    let (min_x, max_x) = pos.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| (lo.min(p.0), hi.max(p.0)));
+    let (min_y, max_y) = pos.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| (lo.min(p.1), hi.max(p.1)));
+    pos.iter().map(|p| (
+        MARGIN + (p.0 - min_x) / (max_x - min_x).max(1.0) * (WIDTH - 2.0 * MARGIN),
+        MARGIN + (p.1 - min_y) / (max_y - min_y).max(1.0) * (HEIGHT - 2.0 * MARGIN),
+    )).collect()
+}
+
+fn radius(node: &Node) -> f64 {
+    let vpc = node.value_per_cost();
+    if vpc.is_nan() { 4.0 } else { (4.0 + vpc * 4.0).clamp(4.0, 14.0) }
+}
+
+fn graph_svg(svg: &mut String, names: &[&String], pos: &[(f64, f64)],
+             nodes: &HashMap<String, Node>, relationships: &Relationships) {
+    writeln!(svg, "<svg id=\"graph\" viewBox=\"0 0 {} {}\">", WIDTH, HEIGHT).unwrap();
+    let index: HashMap<&String, usize> = names.iter().enumerate().map(|(i, n)| (*n, i)).collect();
+    for (from, rel) in relationships {
+        for path in &rel.paths {
+            if let (Some(&a), Some(&b)) = (index.get(from), index.get(&path.to)) {
+                writeln!(svg, "<line class=\"edge\" id=\"e{}-{}\" x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\"><title>{} → {}: {}</title></line>",
+                         a, b, pos[a].0, pos[a].1, pos[b].0, pos[b].1,
+                         escape(from), escape(&path.to), path.cost).unwrap();
+            }
+        }
+    }
+    for (i, name) in names.iter().enumerate() {
+        let node = &nodes[*name];
+        writeln!(svg, "<g class=\"node\" id=\"n{}\"><circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\"><title>{}</title></circle><text x=\"{:.1}\" y=\"{:.1}\">{}</text></g>",
+                 i, pos[i].0, pos[i].1, radius(node), escape(&node.to_string()),
+                 pos[i].0 + radius(node) + 2.0, pos[i].1 + 4.0, escape(name)).unwrap();
+    }
+    writeln!(svg, "</svg>").unwrap();
+}
+
+fn timeline_svg(svg: &mut String, steps: &[Step], budget: i64) {
+    let max_score = steps.iter().map(|s| s.score).max().unwrap_or(0).max(1) as f64;
+    let x = |i: usize| MARGIN + i as f64 / (steps.len().max(2) - 1) as f64 * (WIDTH - 2.0 * MARGIN);
+    let y = |v: f64, max: f64| TIMELINE_HEIGHT - MARGIN - v / max * (TIMELINE_HEIGHT - 2.0 * MARGIN);
+    let line = |value: &dyn Fn(&Step) -> f64, max: f64| steps.iter().enumerate()
+        .map(|(i, s)| format!("{:.1},{:.1}", x(i), y(value(s), max)))
+        .collect::<Vec<_>>().join(" ");
+
+    writeln!(svg, "<svg id=\"timeline\" viewBox=\"0 0 {} {}\">", WIDTH, TIMELINE_HEIGHT).unwrap();
+    writeln!(svg, "<line class=\"axis\" x1=\"{m}\" y1=\"{b}\" x2=\"{r}\" y2=\"{b}\"/><line class=\"axis\" x1=\"{m}\" y1=\"{m}\" x2=\"{m}\" y2=\"{b}\"/>",
+             m = MARGIN, b = TIMELINE_HEIGHT - MARGIN, r = WIDTH - MARGIN).unwrap();
+    writeln!(svg, "<polyline class=\"time\" points=\"{}\"/>", line(&|s| s.time_left as f64, budget.max(1) as f64)).unwrap();
+    writeln!(svg, "<polyline class=\"score\" points=\"{}\"/>", line(&|s| s.score as f64, max_score)).unwrap();
+    writeln!(svg, "<line id=\"cursor\" class=\"cursor\" x1=\"{m}\" y1=\"{m}\" x2=\"{m}\" y2=\"{b}\"/>",
+             m = MARGIN, b = TIMELINE_HEIGHT - MARGIN).unwrap();
+    writeln!(svg, "<text class=\"time\" x=\"{}\" y=\"20\">time left (max {})</text><text class=\"score\" x=\"{}\" y=\"20\">score (max {})</text>",
+             MARGIN, budget, MARGIN + 250.0, max_score).unwrap();
+    writeln!(svg, "<text x=\"{}\" y=\"{}\">hop</text>", WIDTH / 2.0, TIMELINE_HEIGHT - 10.0).unwrap();
+    writeln!(svg, "</svg>").unwrap();
+}
+
+fn decision_table(html: &mut String, steps: &[Step], nodes: &HashMap<String, Node>) {
+    writeln!(html, "<table><tr><th>Step</th><th>Company</th><th>Travel</th><th>Collected</th><th>Swag</th><th>Time price</th><th>Value per cost</th><th>Gained</th><th>Elapsed</th><th>Time left</th><th>Score</th></tr>").unwrap();
+    for (i, step) in steps.iter().enumerate() {
+        let node = &nodes[&step.company];
+        writeln!(html, "<tr id=\"row{}\"><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.2}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                 i, i, escape(&step.company), step.travel, if step.collected { "yes" } else { "no" },
+                 node.value, step.collection_cost, node.value_per_cost(), step.value,
+                 step.elapsed, step.time_left, step.score).unwrap();
+    }
+    writeln!(html, "</table>").unwrap();
+}
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;max-width:1100px}svg{width:100%;border:1px solid #ccc;background:#fafafa}\
+.edge{stroke:#ccc;stroke-width:1}.edge.walked{stroke:#d33;stroke-width:3}.node circle{fill:#8ab;stroke:#456}\
+.node.collected circle{fill:#6c6}.node.current circle{stroke:#d33;stroke-width:4}.node text{font-size:10px}\
+.axis{stroke:#888}.cursor{stroke:#999;stroke-dasharray:4}polyline{fill:none;stroke-width:2}polyline.time{stroke:#36c}\
+polyline.score{stroke:#3a3}text.time{fill:#36c}text.score{fill:#3a3}table{border-collapse:collapse;margin-top:1em}\
+td,th{border:1px solid #ccc;padding:2px 8px;text-align:right}tr.active{background:#fe9}";
+
+const SCRIPT: &str = "const slider=document.getElementById('step'),label=document.getElementById('label');let timer=null;\
+function show(k){document.querySelectorAll('.walked,.collected,.current,.active').forEach(e=>e.classList.remove('walked','collected','current','active'));\
+for(let i=0;i<=k;i++){const s=STEPS[i];if(s.edge){const e=document.getElementById(s.edge);if(e)e.classList.add('walked');}\
+if(s.collected)document.getElementById('n'+s.node).classList.add('collected');}\
+document.getElementById('n'+STEPS[k].node).classList.add('current');document.getElementById('row'+k).classList.add('active');\
+const c=document.getElementById('cursor'),x=X0+(k/Math.max(1,STEPS.length-1))*XW;c.setAttribute('x1',x);c.setAttribute('x2',x);\
+label.textContent='Step '+k+': '+STEPS[k].company+' | score '+STEPS[k].score+' | time left '+STEPS[k].time_left;slider.value=k;}\
+slider.oninput=()=>show(+slider.value);document.getElementById('play').onclick=()=>{if(timer){clearInterval(timer);timer=null;return;}\
+let k=+slider.value>=STEPS.length-1?0:+slider.value;timer=setInterval(()=>{show(k);if(++k>=STEPS.length){clearInterval(timer);timer=null;}},400);};show(0);";
+
+/// A single offline HTML page with the graph laid out as SVG, the route playable step by step,
+/// a timeline of time left and score and a table of every collection decision.
+pub fn to_html(steps: &[Step], nodes: &HashMap<String, Node>, relationships: &Relationships,
+               algorithm: &str, budget: i64) -> String {
+    let mut names: Vec<&String> = nodes.keys().collect();
+    names.sort();
+    let pos = layout(&names, relationships, 1);
+    let index: HashMap<&String, usize> = names.iter().enumerate().map(|(i, n)| (*n, i)).collect();
+    let last = steps.last();
+
+    let mut html = String::new();
+    writeln!(html, "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Route report</title><style>{}</style></head><body>", STYLE).unwrap();
+    writeln!(html, "<h1>Route report</h1><p>Algorithm {} | Score {} | Time left {} of {} | {} hops</p>",
+             escape(algorithm), last.map_or(0, |s| s.score), last.map_or(budget, |s| s.time_left),
+             budget, steps.len().saturating_sub(1)).unwrap();
+    graph_svg(&mut html, &names, &pos, nodes, relationships);
+    writeln!(html, "<p><button id=\"play\">Play / pause</button> <input type=\"range\" id=\"step\" min=\"0\" max=\"{}\" value=\"0\"> <span id=\"label\"></span></p>",
+             steps.len().saturating_sub(1)).unwrap();
+    writeln!(html, "<h2>Timeline</h2>").unwrap();
+    timeline_svg(&mut html, steps, budget);
+    writeln!(html, "<h2>Decisions</h2>").unwrap();
+    decision_table(&mut html, steps, nodes);
+
+    let data: Vec<serde_json::Value> = steps.iter().enumerate().map(|(i, s)| {
+        let node = index[&s.company];
+        let edge = if i > 0 { Some(format!("e{}-{}", index[&steps[i - 1].company], node)) } else { None };
+        serde_json::json!({"node": node, "edge": edge, "collected": s.collected, "company": s.company,
+                           "score": s.score, "time_left": s.time_left})
+    }).collect();
+    writeln!(html, "<script>const STEPS={};const X0={};const XW={};{}</script>",
+             serde_json::Value::from(data).to_string().replace("</", "<\\/"), MARGIN, WIDTH - 2.0 * MARGIN, SCRIPT).unwrap();
+    writeln!(html, "</body></html>").unwrap();
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::route::replay;
+    use crate::value::Additive;
+    use crate::{Action, Path, Relationship, State};
+
+    #[test]
+    fn escapes_names_and_shows_the_timeline_and_decisions() {
+        let nodes: HashMap<String, Node> = [("Neo4j", 0, 0), ("<A&B>", 100, 20)].iter()
+            .map(|(name, value, cost)| (name.to_string(), Node::new(name.to_string(), *value, *cost)))
+            .collect();
+        let mut relationships = Relationships::new();
+        relationships.insert("Neo4j".to_string(), Relationship { paths: vec![Path { to: "<A&B>".to_string(), cost: 10 }] });
+        relationships.insert("<A&B>".to_string(), Relationship { paths: vec![Path { to: "Neo4j".to_string(), cost: 10 }] });
+        let route = [Action::new("Neo4j".to_string(), false), Action::new("<A&B>".to_string(), true),
+                     Action::new("Neo4j".to_string(), false)];
+        let steps = replay(&route, State::starting("Neo4j".to_string(), 100, 4), &nodes, &relationships, &Additive {}).unwrap();
+        let html = to_html(&steps, &nodes, &relationships, "<script>", 100);
+
+        assert!(html.contains("Algorithm &lt;script&gt; | Score 100 | Time left 60 of 100 | 2 hops"));
+        assert!(html.contains("&lt;A&amp;B&gt;"));
+        assert!(!html.contains(">&lt;A&B>") && !html.contains("<td><A&B></td>"));
+        assert!(html.contains("<svg id=\"timeline\""));
+        assert_eq!(html.matches("<polyline class=").count(), 2);
+        assert!(html.contains("<tr id=\"row1\"><td>1</td><td>&lt;A&amp;B&gt;</td><td>10</td><td>yes</td><td>100</td>\
+                               <td>20</td><td>5.00</td><td>100</td><td>30</td><td>70</td><td>100</td></tr>"));
+        assert!(html.contains("<tr id=\"row2\"><td>2</td><td>Neo4j</td><td>10</td><td>no</td>"));
+        assert!(!html.contains("row3"));
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+
+use serde_json::json;
+
+use crate::{Action, Node, Path, Relationships, State};
+use crate::value::ValueModel;
+
+/// One hop of a replayed route, with the state after it.
+pub struct Step {
+    pub(crate) company: String,
+    pub(crate) travel: i64,
+    pub(crate) collected: bool,
+    pub(crate) value: i64, // Score gained by collecting here.
+    pub(crate) collection_cost: i64,
+    pub(crate) time_left: i64,
+    pub(crate) elapsed: i64,
+    pub(crate) score: i64,
+}
+
+/// The route walked so far, with everything `state_from_json` needs to carry on from its end.
+pub fn route_to_json(state: &State, algorithm: &str) -> serde_json::Value {
+    json!({
+        "algorithm": algorithm,
+        "rules": state.rules.to_string(),
+        "score": state.score,
+        "time_left": state.time_left,
+        "elapsed": state.elapsed,
+        "ttl": state.ttl,
+        "company": state.current_company,
+        "steps": state.last_companies.iter()
+            .map(|a| json!({"company": a.company, "collected": a.collected}))
+            .collect::<Vec<_>>(),
+    })
+}
+
+pub fn route_from_json(json: &serde_json::Value) -> Result<Vec<Action>, String> {
+    json["steps"].as_array().ok_or("Route has no steps.")?.iter().map(|step| {
+        let company = step["company"].as_str().ok_or("Step without company.")?;
+        Ok(Action::new(company.to_string(), step["collected"].as_bool().unwrap_or(false)))
+    }).collect()
+}
+
+/// The state at the end of a saved route, to resume solving or walking from.
+pub fn state_from_json(json: &serde_json::Value) -> Result<State, String> {
+    let number = |key: &str| json[key].as_i64()
+        .ok_or(format!("Route has no {}, it was saved before routes could be resumed.", key));
+    let route = route_from_json(json)?;
+    let company = match json["company"].as_str() {
+        Some(company) => company.to_string(),
+        None => route.last().ok_or("Route is empty.")?.company.clone(),
+    };
+    let ttl = i32::try_from(number("ttl")?).map_err(|_| "Route has an invalid ttl.")?;
+    let mut state = State::starting(company, number("time_left")?, ttl);
+    if let Some(rules) = json["rules"].as_str() {
+        state.rules = rules.parse()?;
+    }
+    state.score = number("score")?;
+    state.elapsed = number("elapsed")?;
+    for action in route {
+        state.record(action);
+    }
+    Ok(state)
+}
+
+/// The cheapest path between two neighbouring companies.
+pub fn find_path<'a>(relationships: &'a Relationships, from: &str, to: &str) -> Option<&'a Path> {
+    relationships.get(from)?.paths.iter()
+        .filter(|p| p.to == to)
+        .min_by_key(|p| p.cost)
+}
+
+/// Walks the route from `start` by the rules of that state, as the solvers do, which also
+/// verifies it: every hop must follow an existing path, and fit in the time if the rules end the
+/// walk when the time runs out.
+pub fn replay(route: &[Action], state: State, nodes: &HashMap<String, Node>,
+              relationships: &Relationships, model: &dyn ValueModel) -> Result<Vec<Step>, String> {
+    walk(route, state, nodes, relationships, model).map(|(steps, _)| steps)
+}
+
+/// Like `replay`, also giving the state at the end of the route.
+pub fn walk(route: &[Action], mut state: State, nodes: &HashMap<String, Node>,
+            relationships: &Relationships, model: &dyn ValueModel) -> Result<(Vec<Step>, State), String> {
+    let mut steps = vec![];
+    let first = route.first().ok_or("Route is empty.")?;
+    let start = nodes.get(&first.company).ok_or(format!("Unknown company {}.", first.company))?;
+    state.current_company = first.company.clone();
+
+    let before = state.clone();
+    let collected = first.collected && state.force_collect(start, nodes, model);
+    state.record(Action::new(first.company.clone(), collected));
+    steps.push(step_from(&before, &state, 0, start.cost));
+
+    for action in &route[1..] {
+        let path = find_path(relationships, &state.current_company, &action.company)
+            .ok_or(format!("There is no path from {} to {}.", state.current_company, action.company))?;
+        let before = state.clone();
+        state.goto(path, nodes, model, action.collected);
+        if state.last_companies.len() == before.last_companies.len() {
+            return Err(format!("The hop to {} does not fit in the {} time left.", action.company, before.time_left));
+        }
+        steps.push(step_from(&before, &state, path.cost, nodes[&action.company].cost));
+    }
+    Ok((steps, state))
+}
+
+fn step_from(before: &State, after: &State, travel: i64, cost: i64) -> Step {
+    let action = after.last_companies.last().unwrap();
+    let value = after.score - before.score;
+    Step {
+        company: action.company.clone(),
+        travel,
+        collected: action.collected,
+        value,
+        collection_cost: cost,
+        time_left: after.time_left,
+        elapsed: after.elapsed,
+        score: after.score,
+    }
+}
@@ -0,0 +1,95 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// What revisiting a company means. Every revisit uses up one `ttl` either way, the solvers
+/// stop looking ahead when it runs out.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Revisits {
+    // Once the ttl is used up nothing more is collected.
+    Ttl,
+    // Revisits never stop collection.
+    Free,
+}
+
+/// When a collection is booked relative to the hop to the company. Either way it has to fit
+/// together with that hop.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Collection {
+    // Booked before the travel, collecting at the start needs twice its time left.
+    BeforeTravel,
+    // Travel first, then collect. Collecting at the start needs just its own time.
+    AfterTravel,
+}
+
+/// What a hop which does not fit in the time left does.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OutOfTime {
+    // The hop is recorded but not made, the time left is lost.
+    Stay,
+    // The walk ends where it is, the hop is not part of the route.
+    End,
+}
+
+/// The rules a walk is played by, shared by the solvers and the verifier.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Rules {
+    pub revisits: Revisits,
+    pub collection: Collection,
+    pub out_of_time: OutOfTime,
+}
+
+impl Rules {
+    /// The rules the solvers have always played by.
+    pub fn legacy() -> Rules {
+        Rules { revisits: Revisits::Ttl, collection: Collection::BeforeTravel, out_of_time: OutOfTime::Stay }
+    }
+
+    /// The rules of the graphathon: revisit freely, collect on arrival if the time allows and the
+    /// route ends with the last hop that fits.
+    pub fn official() -> Rules {
+        Rules { revisits: Revisits::Free, collection: Collection::AfterTravel, out_of_time: OutOfTime::End }
+    }
+
+    /// Whether a collection is still allowed with `ttl` left.
+    pub fn may_collect(&self, ttl: i32) -> bool {
+        self.revisits == Revisits::Free || ttl > 0
+    }
+
+    /// Whether collecting at the start fits, before any travel.
+    pub fn start_collection_fits(&self, cost: i64, waiting: i64, time_left: i64) -> bool {
+        match self.collection {
+            Collection::BeforeTravel => cost + cost + waiting <= time_left,
+            Collection::AfterTravel => cost + waiting <= time_left,
+        }
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules::legacy()
+    }
+}
+
+impl FromStr for Rules {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "legacy" => Ok(Rules::legacy()),
+            "official" => Ok(Rules::official()),
+            other => Err(format!("Unknown rules {}, expected legacy or official", other)),
+        }
+    }
+}
+
+impl Display for Rules {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if *self == Rules::legacy() {
+            write!(f, "legacy")
+        } else if *self == Rules::official() {
+            write!(f, "official")
+        } else {
+            write!(f, "{:?}/{:?}/{:?}", self.revisits, self.collection, self.out_of_time)
+        }
+    }
+}
@@ -2,6 +2,10 @@ use core::fmt::Formatter;
 use core::fmt::Display;
 use std::collections::HashMap;
 use crate::{action, Node, Path};
+use crate::companies::CompanySet;
+use crate::node::NodeKind;
+use crate::rules::{Collection, OutOfTime, Rules};
+use crate::value::ValueModel;
 
 use action::Action;
 
@@ -10,7 +14,22 @@ pub struct State {
     pub(crate) last_companies: Vec<Action>,
     pub(crate) score: i64,
     pub(crate) time_left: i64,
-    pub(crate) ttl: i32 // Maximum amount of steps over already entered nodes.
+    pub(crate) elapsed: i64, // Time spent since the start, including waiting for booths to open.
+    pub(crate) ttl: i32, // Maximum amount of steps over already entered nodes.
+    pub(crate) rules: Rules,
+    visited: CompanySet,
+    collected: CompanySet
+}
+
+/// What `State::apply` changed, to take the move back with `State::undo`.
+pub struct Undo {
+    company: Option<String>, // Where we were, if we moved.
+    score: i64,
+    time_left: i64,
+    elapsed: i64,
+    ttl: i32,
+    recorded: bool,
+    first_visit: bool
 }
 
 impl Clone for State {
@@ -18,9 +37,13 @@ impl Clone for State {
         Self {
             current_company: self.current_company.clone(),
             last_companies: self.last_companies.clone(),
-            score: self.score.clone(),
-            time_left: self.time_left.clone(),
-            ttl: self.ttl.clone()
+            score: self.score,
+            time_left: self.time_left,
+            elapsed: self.elapsed,
+            ttl: self.ttl,
+            rules: self.rules,
+            visited: self.visited.clone(),
+            collected: self.collected.clone()
         }
     }
 }
@@ -28,45 +51,170 @@ impl Clone for State {
 
 impl State {
     pub fn new(ttl: i32) -> State {
+        State::starting("Neo4j".to_string(), 4500, ttl)
+    }
+
+    /// A fresh state at `company` with `budget` time to spend.
+    pub fn starting(company: String, budget: i64, ttl: i32) -> State {
         State{
-            current_company: "Neo4j".to_string(),
+            current_company: company,
             last_companies: vec![],
-            score: 0, time_left: 4500, ttl
+            score: 0, time_left: budget, elapsed: 0, ttl, rules: Rules::default(),
+            visited: CompanySet::default(), collected: CompanySet::default()
         }
     }
 
-    pub(crate) fn force_collect(&mut self, node: &Node) {
-        if node.cost + node.cost <= self.time_left{
-            self.score += node.value;
-            self.time_left -= node.cost;
-        }
+    pub fn with_rules(mut self, rules: Rules) -> State {
+        self.rules = rules;
+        self
     }
 
-    pub fn goto(&mut self, path_followed: &Path, nodes: &HashMap<String, Node>, collect: bool) {
-        let name = path_followed.to.clone();
-        if self.last_companies.iter().find(|a| &a.company == &name).is_some() {
-            self.ttl -= 1;
+    /// Indexes the companies, so that searching from clones of this state never copies the index.
+    pub fn index(&mut self, nodes: &HashMap<String, Node>) {
+        self.visited.index(nodes.keys());
+        self.collected.index(nodes.keys());
+    }
+
+    /// Appends to the route, as the first company or a hop which was made elsewhere.
+    pub fn record(&mut self, action: Action) {
+        self.visited.insert(&action.company);
+        if action.collected {
+            self.collected.insert(&action.company);
         }
+        self.last_companies.push(action);
+    }
 
-        self.last_companies.push(
-            Action{ company: name.clone(),
-                collected: collect });
+    /// Stands still for `duration`, or until the time runs out.
+    pub fn wait(&mut self, duration: i64) {
+        let duration = duration.clamp(0, self.time_left.max(0));
+        self.time_left -= duration;
+        self.elapsed += duration;
+    }
 
-        if collect && self.ttl > 0 {
-            let temp_node = nodes.get(&name).unwrap();
+    pub fn has_visited(&self, company: &str) -> bool {
+        self.visited.contains(company)
+    }
 
-            if temp_node.cost + path_followed.cost <= self.time_left{
-                self.score += temp_node.value;
-                self.time_left -= temp_node.cost;
+    /// Whether the walk is over, by the rules which end it with the hop that does not fit.
+    pub fn ended(&self) -> bool {
+        self.rules.out_of_time == OutOfTime::End && self.time_left <= 0
+    }
+
+    /// Whether swag has been collected at the company on this walk.
+    pub fn has_collected(&self, company: &str) -> bool {
+        self.collected.contains(company)
+    }
+
+    /// Collects at the current company if it is open now or opens in time, returns whether it did.
+    pub(crate) fn force_collect(&mut self, node: &Node, nodes: &HashMap<String, Node>, model: &dyn ValueModel) -> bool {
+        if node.kind() == NodeKind::PassThrough || self.has_collected(&node.name) {
+            return false;
+        }
+        if let Some(start) = node.collection_start(self.elapsed) {
+            let waiting = start - self.elapsed;
+            if self.rules.start_collection_fits(node.cost, waiting, self.time_left) {
+                self.wait(waiting);
+                self.score += model.marginal_value(node, &self.last_companies, nodes);
+                self.time_left -= node.cost;
+                self.elapsed += node.cost;
+                return true;
             }
         }
+        false
+    }
 
-        if self.time_left < path_followed.cost {
-            self.time_left = 0;
-        } else {
+    /// Records what actually happened on the day: we ended up at `company` after `time_spent`
+    /// (travel, queueing and collecting) and did or did not get the swag. Swag reported again
+    /// where it was collected already counts once.
+    pub fn observe(&mut self, company: &str, time_spent: i64, collected: bool,
+                   nodes: &HashMap<String, Node>, model: &dyn ValueModel) {
+        if self.visited.contains(company) {
+            self.ttl -= 1;
+        }
+        let collected = collected && !self.has_collected(company);
+        if collected {
+            self.score += model.marginal_value(nodes.get(company).unwrap(), &self.last_companies, nodes);
+        }
+        self.record(Action::new(company.to_string(), collected));
+        self.time_left = (self.time_left - time_spent).max(0);
+        self.elapsed += time_spent;
+        self.current_company = company.to_string();
+    }
+
+    /// Follows the path and collects at its end if asked to and the rules allow it. The action
+    /// records whether swag was actually collected.
+    pub fn goto(&mut self, path_followed: &Path, nodes: &HashMap<String, Node>, model: &dyn ValueModel, collect: bool) {
+        self.apply(path_followed, nodes, model, collect);
+    }
+
+    /// Like `goto`, returning what is needed to undo the move in constant time.
+    pub fn apply(&mut self, path_followed: &Path, nodes: &HashMap<String, Node>, model: &dyn ValueModel, collect: bool) -> Undo {
+        let mut undo = Undo{ company: None, score: self.score, time_left: self.time_left, elapsed: self.elapsed,
+                             ttl: self.ttl, recorded: false, first_visit: false };
+        let name = &path_followed.to;
+        let fits = path_followed.cost <= self.time_left;
+        if !fits && self.rules.out_of_time == OutOfTime::End {
+            self.wait(self.time_left);
+            return undo;
+        }
+        if self.visited.contains(name) {
+            self.ttl -= 1;
+        }
+
+        let temp_node = nodes.get(name).unwrap();
+        let arrival = self.elapsed + path_followed.cost;
+        // Booths which have closed before we get there, bad swag and swag we already got can not be collected.
+        let waiting = temp_node.collection_start(arrival).map(|start| start - arrival);
+        let collect = match waiting {
+            Some(waiting) => collect && temp_node.kind() != NodeKind::PassThrough && !self.has_collected(name)
+                && self.rules.may_collect(self.ttl)
+                && temp_node.cost + path_followed.cost + waiting <= self.time_left,
+            None => false,
+        };
+        let collect_here = |state: &mut State| if collect {
+            state.wait(waiting.unwrap());
+            state.score += model.marginal_value(temp_node, &state.last_companies, nodes);
+            state.time_left -= temp_node.cost;
+            state.elapsed += temp_node.cost;
+        };
+
+        if self.rules.collection == Collection::BeforeTravel {
+            collect_here(self);
+        }
+        if fits {
             self.time_left -= path_followed.cost;
-            self.current_company = name;
+            self.elapsed += path_followed.cost;
+            undo.company = Some(std::mem::replace(&mut self.current_company, name.clone()));
+        } else {
+            self.wait(self.time_left);
+        }
+        if self.rules.collection == Collection::AfterTravel {
+            collect_here(self);
+        }
+        undo.recorded = true;
+        undo.first_visit = !self.visited.contains(name);
+        self.record(Action{ company: name.clone(), collected: collect });
+        undo
+    }
+
+    /// Takes back the last move, given what its `apply` returned.
+    pub fn undo(&mut self, undo: Undo) {
+        if undo.recorded {
+            let action = self.last_companies.pop().unwrap();
+            if action.collected {
+                self.collected.remove(&action.company);
+            }
+            if undo.first_visit {
+                self.visited.remove(&action.company);
+            }
+        }
+        if let Some(company) = undo.company {
+            self.current_company = company;
         }
+        self.score = undo.score;
+        self.time_left = undo.time_left;
+        self.elapsed = undo.elapsed;
+        self.ttl = undo.ttl;
     }
 
 }
@@ -74,11 +222,241 @@ impl State {
 impl Display for State {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "\nStart {}\n", self.current_company)?;
-        write!(f, "Score: {}, Time left: {}\n", self.score, self.time_left)?;
+        writeln!(f, "Score: {}, Time left: {}, Elapsed: {}", self.score, self.time_left, self.elapsed)?;
         for act in &self.last_companies {
             write!(f, "{}-->", act)?;
         }
-        write!(f, "({}:?)\n", self.current_company)?;
+        writeln!(f, "({}:?)", self.current_company)?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::{generate, GeneratorConfig};
+    use crate::rng::Rng;
+    use crate::value::Additive;
+
+    fn nodes() -> HashMap<String, Node> {
+        vec![Node::new("Neo4j".to_string(), 350, 147),
+             Node::new("BadCorp".to_string(), -1, 95),
+             Node::new("Free".to_string(), 20, 0)]
+            .into_iter().map(|n| (n.name.clone(), n)).collect()
+    }
+
+    fn path(to: &str) -> Path {
+        Path{ to: to.to_string(), cost: 10 }
+    }
+
+    #[test]
+    fn never_collects_negative_swag() {
+        let nodes = nodes();
+        let mut state = State::new(4);
+        state.goto(&path("BadCorp"), &nodes, &Additive {}, true);
+        assert_eq!(state.score, 0);
+        assert_eq!(state.time_left, 4490);
+        assert!(!state.last_companies.last().unwrap().collected);
+        assert!(!state.force_collect(nodes.get("BadCorp").unwrap(), &nodes, &Additive {}));
+    }
+
+    #[test]
+    fn collects_free_swag_without_spending_time() {
+        let nodes = nodes();
+        let mut state = State::new(4);
+        state.goto(&path("Free"), &nodes, &Additive {}, true);
+        assert_eq!(state.score, 20);
+        assert_eq!(state.time_left, 4490);
+        assert!(state.last_companies.last().unwrap().collected);
+    }
+
+    #[test]
+    fn never_collects_twice() {
+        let nodes = nodes();
+        let mut state = State::new(4);
+        assert!(state.force_collect(&nodes["Neo4j"], &nodes, &Additive {}));
+        state.record(Action::new("Neo4j".to_string(), true));
+        state.goto(&path("Free"), &nodes, &Additive {}, true);
+        state.goto(&path("Neo4j"), &nodes, &Additive {}, true);
+        assert_eq!(state.score, 370);
+        assert!(!state.last_companies.last().unwrap().collected);
+        assert!(!state.force_collect(&nodes["Neo4j"], &nodes, &Additive {}));
+    }
+
+    fn booth(earliest: i64, latest: i64) -> HashMap<String, Node> {
+        let booth = Node::new("Booth".to_string(), 100, 20).with_window(crate::node::TimeWindow::new(earliest, latest));
+        let mut nodes = nodes();
+        nodes.insert(booth.name.clone(), booth);
+        nodes
+    }
+
+    #[test]
+    fn waits_for_a_booth_to_open() {
+        let nodes = booth(50, 200);
+        let mut state = State::starting("Neo4j".to_string(), 300, 4);
+        state.goto(&path("Booth"), &nodes, &Additive {}, true);
+        // Arriving at 10, waiting 40 and collecting for 20.
+        assert!(state.last_companies.last().unwrap().collected);
+        assert_eq!((state.score, state.elapsed, state.time_left), (100, 70, 230));
+    }
+
+    #[test]
+    fn closed_booths_are_not_collected() {
+        let nodes = booth(0, 5);
+        let mut state = State::starting("Neo4j".to_string(), 300, 4);
+        state.goto(&path("Booth"), &nodes, &Additive {}, true);
+        assert!(!state.last_companies.last().unwrap().collected);
+        assert_eq!((state.score, state.elapsed, state.time_left), (0, 10, 290));
+    }
+
+    #[test]
+    fn a_hop_which_does_not_fit_ends_the_walk() {
+        let nodes = booth(0, 400);
+        for (rules, ended) in [(Rules::official(), true), (Rules::legacy(), false)] {
+            let mut state = State::starting("Neo4j".to_string(), 5, 4).with_rules(rules);
+            assert!(!state.ended());
+            state.goto(&path("Booth"), &nodes, &Additive {}, true);
+            assert_eq!((state.time_left, state.ended()), (0, ended));
+        }
+    }
+
+    #[test]
+    fn never_waits_past_the_budget() {
+        // Opening at 250 leaves too little time to collect, so there is no waiting either.
+        let nodes = booth(250, 400);
+        let mut state = State::starting("Neo4j".to_string(), 260, 4);
+        state.goto(&path("Booth"), &nodes, &Additive {}, true);
+        assert!(!state.last_companies.last().unwrap().collected);
+        assert_eq!((state.score, state.elapsed, state.time_left), (0, 10, 250));
+
+        // At the start as well, and waiting never takes more than the time left.
+        let mut start = State::starting("Booth".to_string(), 100, 4);
+        assert!(!start.force_collect(&nodes["Booth"], &nodes, &Additive {}));
+        assert_eq!((start.elapsed, start.time_left), (0, 100));
+        start.wait(500);
+        assert_eq!((start.elapsed, start.time_left), (100, 0));
+        let mut start = State::starting("Booth".to_string(), 300, 4);
+        assert!(start.force_collect(&nodes["Booth"], &nodes, &Additive {}));
+        assert_eq!((start.elapsed, start.time_left), (270, 30));
+    }
+
+    #[test]
+    fn observed_swag_counts_once() {
+        let nodes = nodes();
+        let mut state = State::new(4);
+        state.record(Action::new("Free".to_string(), false));
+        state.observe("Neo4j", 200, true, &nodes, &Additive {});
+        state.observe("Neo4j", 10, true, &nodes, &Additive {});
+        assert_eq!((state.score, state.time_left, state.ttl), (350, 4290, 3));
+        assert!(!state.last_companies.last().unwrap().collected);
+        assert_eq!(state.last_companies.iter().filter(|a| a.collected).count(), 1);
+    }
+
+    #[test]
+    fn official_rules_differ_from_legacy() {
+        let nodes = nodes();
+        // Collecting at the start only needs its own time.
+        let mut legacy = State::starting("Neo4j".to_string(), 200, 0);
+        let mut official = legacy.clone().with_rules(Rules::official());
+        assert!(!legacy.force_collect(&nodes["Neo4j"], &nodes, &Additive {}));
+        assert!(official.force_collect(&nodes["Neo4j"], &nodes, &Additive {}));
+
+        // Revisits do not stop collection.
+        legacy.record(Action::new("Free".to_string(), false));
+        official.record(Action::new("Free".to_string(), false));
+        legacy.goto(&path("Free"), &nodes, &Additive {}, true);
+        official.goto(&path("Free"), &nodes, &Additive {}, true);
+        assert!(!legacy.last_companies.last().unwrap().collected);
+        assert!(official.last_companies.last().unwrap().collected);
+        assert_eq!(official.score, 370);
+
+        // A hop that does not fit ends the walk instead of being recorded.
+        let (legacy_hops, official_hops) = (legacy.last_companies.len(), official.last_companies.len());
+        let far = Path{ to: "Neo4j".to_string(), cost: 1000 };
+        legacy.goto(&far, &nodes, &Additive {}, false);
+        official.goto(&far, &nodes, &Additive {}, false);
+        assert_eq!(legacy.last_companies.len(), legacy_hops + 1);
+        assert_eq!(official.last_companies.len(), official_hops);
+        assert_eq!((legacy.time_left, official.time_left), (0, 0));
+    }
+
+    fn assert_same(state: &State, expected: &State, nodes: &HashMap<String, Node>) {
+        assert!(state.current_company == expected.current_company && state.last_companies == expected.last_companies);
+        assert_eq!((state.score, state.time_left, state.elapsed, state.ttl),
+                   (expected.score, expected.time_left, expected.elapsed, expected.ttl));
+        for name in nodes.keys() {
+            assert_eq!(state.has_collected(name), expected.has_collected(name));
+            assert_eq!(state.visited.contains(name), expected.visited.contains(name));
+        }
+    }
+
+    /// Random walks asking for random collections on random graphs, like a solver might, sometimes
+    /// taking moves back.
+    #[test]
+    fn random_walks_keep_the_invariants() {
+        let mut rng = Rng::new(1);
+        for case in 0..300 {
+            let config = GeneratorConfig {
+                nodes: 2 + rng.below(20),
+                clusters: 1 + rng.below(4),
+                negative: rng.next_f64() * 0.3,
+                symmetric: rng.below(2) == 0,
+                seed: case,
+                ..GeneratorConfig::default()
+            };
+            let (nodes, relationships) = generate(&config);
+            let budget = rng.below(3000) as i64;
+            let rules = if rng.below(2) == 0 { Rules::legacy() } else { Rules::official() };
+            let mut state = State::starting("Neo4j".to_string(), budget, 1 + rng.below(6) as i32).with_rules(rules);
+            let mut collections: HashMap<String, usize> = HashMap::new();
+
+            let collected = rng.below(2) == 0 && state.force_collect(&nodes["Neo4j"], &nodes, &Additive {});
+            state.record(Action::new("Neo4j".to_string(), collected));
+            if collected {
+                collections.insert("Neo4j".to_string(), 1);
+            }
+            assert!(state.time_left >= 0);
+
+            for _ in 0..80 {
+                let paths = &relationships[&state.current_company].paths;
+                let path = &paths[rng.below(paths.len())];
+                let before = state.clone();
+                let undo = state.apply(path, &nodes, &Additive {}, rng.below(3) != 0);
+                if state.last_companies.len() == before.last_companies.len() {
+                    // The walk ended, only the time left is gone.
+                    assert_eq!(rules.out_of_time, OutOfTime::End, "case {}: unrecorded hop", case);
+                    assert!(path.cost > before.time_left);
+                    assert_eq!((state.time_left, state.score), (0, before.score));
+                    break;
+                }
+
+                let action = state.last_companies.last().unwrap();
+                assert_eq!(action.company, path.to);
+                assert!(state.time_left >= 0, "case {}: time left {}", case, state.time_left);
+                assert_eq!(state.time_left + state.elapsed, budget, "case {}: time is lost", case);
+                if nodes[&path.to].value >= 0 {
+                    assert!(state.score >= before.score, "case {}: score fell at {}", case, path.to);
+                }
+                let revisit = before.last_companies.iter().any(|a| a.company == path.to);
+                assert_eq!(before.ttl - state.ttl, revisit as i32, "case {}: ttl at {}", case, path.to);
+                // Spending more than the travel means swag was collected.
+                if state.score != before.score || before.time_left - state.time_left > path.cost.min(before.time_left) {
+                    assert!(action.collected, "case {}: unrecorded collection at {}", case, path.to);
+                    *collections.entry(path.to.clone()).or_default() += 1;
+                }
+                // Sometimes take the move back, as the searches do.
+                if rng.below(4) == 0 {
+                    if action.collected {
+                        *collections.get_mut(&path.to).unwrap() -= 1;
+                    }
+                    state.undo(undo);
+                    assert_same(&state, &before, &nodes);
+                }
+            }
+            assert!(collections.values().all(|c| *c <= 1), "case {}: collected twice", case);
+            for name in nodes.keys() {
+                assert!(state.last_companies.iter().filter(|a| a.collected && &a.company == name).count() <= 1);
+            }
+        }
+    }
+}
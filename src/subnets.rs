@@ -3,8 +3,7 @@ use std::fmt::{Display, Formatter};
 use std::hash::Hash;
 
 use crate::{Node, Relationship, Relationships};
-use crate::Path;
-use crate::graphs;
+use crate::node::ratio;
 
 #[derive(Hash, PartialEq, Eq)]
 pub struct Subnet <K> {
@@ -27,33 +26,34 @@ impl Subnet<f64> {
     fn value_per_cost_all(nodes: &Vec<Node>) -> Vec<f64> {
         let mut vals = vec![];
         for node in nodes {
-            vals.push((node.value as f64)/(node.cost as f64));
+            vals.push(node.value_per_cost());
         }
         vals
     }
 
     pub fn value_per_cost(&self) -> f64 {
-        let mut total_value = 0.0;
-        let mut total_cost = 0.0;
+        let mut total_value = 0;
+        let mut total_cost = 0;
 
         for node in &self.nodes {
-            total_cost += node.cost as f64;
-            total_value += node.value as f64;
+            total_cost += node.cost;
+            total_value += node.value;
         }
 
-        total_value/total_cost
+        ratio(total_value, total_cost)
     }
 
-    pub fn best_node(&self) -> &Node {
+    /// The node with the highest positive value per cost, None if no node is worth collecting.
+    pub fn best_node(&self) -> Option<&Node> {
         let mut max = 0.0;
-        let mut max_index = 0;
+        let mut max_index = None;
         for (i, score) in self.node_values.iter().enumerate() {
             if score > &max {
-                max = score.clone();
-                max_index = i;
+                max = *score;
+                max_index = Some(i);
             }
         }
-        self.nodes.get(max_index).unwrap()
+        max_index.map(|i| &self.nodes[i])
     }
 }
 
@@ -63,6 +63,7 @@ pub struct Subnets <K> {
 }
 
 
+#[allow(dead_code)]
 impl<K> Subnets<K> {
     pub fn get_subnet_from_node(&self, node: &Node) -> Option<usize> {
         for (i, subnet) in self.subnets.iter().enumerate() {
@@ -93,7 +94,8 @@ impl<K> Subnets<K> {
 
             let mut found = vec![];
             for node in &subnet.nodes {
-                let rels = &relationships.get(&node.name).unwrap().paths;
+                // Companies without outgoing paths may have no entry at all.
+                let rels = relationships.get(&node.name).map(|rel| &rel.paths[..]).unwrap_or_default();
                 for path in rels {
                     if subnodes.contains(&path.to) {
                         found.push(path.clone());
@@ -110,7 +112,7 @@ impl<K> Subnets<K> {
         neighbours
     }
 }
-/**
+/*
 impl <T:Display> Display for Subnets<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         for subnet in &self.subnets {
@@ -121,16 +123,19 @@ impl <T:Display> Display for Subnets<T> {
         }
         Ok(())
     }
-}**/
+}*/
 
 impl Display for Subnets<f64> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         for (i, subnet) in self.subnets.iter().enumerate() {
-            write!(f, "ValuePerCost {} for group {}: ", subnet.value_per_cost(), i);
+            write!(f, "ValuePerCost {} for group {}: ", subnet.value_per_cost(), i)?;
             for node in &subnet.nodes {
-                write!(f, "--{}--", &node.name);
+                write!(f, "--{}--", &node.name)?;
+            }
+            match subnet.best_node() {
+                Some(best) => writeln!(f, " BestNode: {}({}) ", best.name, best.value_per_cost())?,
+                None => writeln!(f, " BestNode: none ")?,
             }
-            write!(f, " BestNode: {}({}) \n", subnet.best_node().name, subnet.best_node().value_per_cost());
         }
         Ok(())
     }
@@ -150,9 +155,11 @@ impl Subnets <f64> {
                                 nodes: &HashMap<String, Node>) -> (HashSet<Node>, HashSet<Node>) {
             let mut set = HashSet::new();
             let mut set_all = HashSet::new();
-            for path in &relationships.get(&from.name).unwrap().paths { //FROM -> A
+            // Companies without outgoing paths may have no entry at all.
+            let paths = |company: &String| relationships.get(company).map(|rel| &rel.paths[..]).unwrap_or_default();
+            for path in paths(&from.name) { //FROM -> A
                 set_all.insert(nodes.get(&path.to).unwrap().clone());
-                for path2 in &relationships.get(&path.to).unwrap().paths { // A -> B
+                for path2 in paths(&path.to) { // A -> B
                     set_all.insert(nodes.get(&path2.to).unwrap().clone());
                     set.insert(nodes.get(&path2.to).unwrap().clone());
                 }
@@ -163,7 +170,7 @@ impl Subnets <f64> {
         let mut bucket_index = 0;
         let mut bucket: Vec<Vec<Node>> = vec![];
 
-        for (company, path) in relationships {
+        for company in relationships.keys() {
             let node = nodes.get(company).expect("Wtf spooky.");
             if !placed.contains(node) {
                 let (set, all) = neighbors_2away(node, relationships, nodes);
@@ -175,7 +182,7 @@ impl Subnets <f64> {
                     for n in &all {
                         if n != node && !placed.contains(n) {
                             let mut found = 0;
-                            for to in relationships.get(&n.name) {
+                            if let Some(to) = relationships.get(&n.name) {
                                 for path in &to.paths { // All paths from current node which was two from start
                                     if set.contains(nodes.get(&path.to).unwrap()) {
                                         found += 1;
@@ -195,8 +202,47 @@ impl Subnets <f64> {
             }
         }
 
-        Self { subnets: bucket.iter().map(|v| Subnet::new(v)).collect() }
+        Self { subnets: bucket.iter().map(Subnet::new).collect() }
+    }
+
+
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Path;
+
+    #[test]
+    fn best_node_is_none_when_nothing_is_worth_collecting() {
+        let subnet = Subnet::new(&vec![Node::new("BadCorp".to_string(), -1, 95),
+                                       Node::new("WorseCorp".to_string(), -5, 10)]);
+        assert!(subnet.best_node().is_none());
+    }
+
+    #[test]
+    fn best_node_skips_negative_and_prefers_free() {
+        let subnet = Subnet::new(&vec![Node::new("BadCorp".to_string(), -1, 0),
+                                       Node::new("Neo4j".to_string(), 350, 147),
+                                       Node::new("Free".to_string(), 1, 0)]);
+        assert_eq!(subnet.best_node().unwrap().name, "Free");
     }
 
+    #[test]
+    fn zero_cost_subnet_has_defined_value_per_cost() {
+        let subnet = Subnet::new(&vec![Node::new("Free".to_string(), 3, 0)]);
+        assert_eq!(subnet.value_per_cost(), f64::INFINITY);
+    }
 
-}
\ No newline at end of file
+    #[test]
+    fn companies_without_paths_are_no_subnet() {
+        let nodes: HashMap<String, Node> = ["A", "B", "Dead"].iter()
+            .map(|name| (name.to_string(), Node::new(name.to_string(), 10, 1))).collect();
+        let path = |to: &str| Path { to: to.to_string(), cost: 1 };
+        // Dead has no entry at all.
+        let relationships: Relationships = HashMap::from([
+            ("A".to_string(), Relationship { paths: vec![path("B"), path("Dead")] }),
+            ("B".to_string(), Relationship { paths: vec![path("A"), path("Dead")] }),
+        ]);
+        assert!(Subnets::new(&relationships, &nodes).subnets.is_empty());
+    }
+}
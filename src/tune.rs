@@ -0,0 +1,237 @@
+use std::str::FromStr;
+
+use serde_json::{json, Value};
+
+use crate::compare::{run, Instance};
+use crate::config::RunConfig;
+use crate::rng::Rng;
+
+/// Values a parameter may take, a list `a,b,c` or an interval `min..max`.
+pub enum Range {
+    Values(Vec<f64>),
+    Interval(f64, f64),
+}
+
+impl FromStr for Range {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = |n: &str| n.trim().parse::<f64>().map_err(|_| format!("{} is not a list a,b,c or an interval min..max", s));
+        match s.split_once("..") {
+            Some((min, max)) => {
+                let (min, max) = (number(min)?, number(max)?);
+                if min > max {
+                    return Err(format!("{} is an empty interval", s));
+                }
+                Ok(Range::Interval(min, max))
+            }
+            None => s.split(',').map(number).collect::<Result<_, _>>().map(Range::Values),
+        }
+    }
+}
+
+impl Range {
+    /// The listed values, or `steps` evenly spaced points of the interval.
+    fn grid(&self, steps: usize) -> Vec<f64> {
+        match self {
+            Range::Values(values) => values.clone(),
+            Range::Interval(min, max) if steps <= 1 => vec![(min + max) / 2.0],
+            Range::Interval(min, max) => (0..steps).map(|i| min + (max - min) * i as f64 / (steps - 1) as f64).collect(),
+        }
+    }
+
+    fn sample(&self, rng: &mut Rng) -> f64 {
+        match self {
+            Range::Values(values) => values[rng.below(values.len())],
+            Range::Interval(min, max) => min + (max - min) * rng.next_f64(),
+        }
+    }
+}
+
+/// A parameter of `algorithm_from_spec` to tune.
+pub struct Parameter {
+    pub name: String,
+    pub range: Range,
+    pub integer: bool,
+}
+
+pub enum Search {
+    // Every combination, intervals split into `steps` points.
+    Grid { steps: usize },
+    Random { samples: usize },
+    // Random candidates on few instances, the better half on twice as many until one is left.
+    Halving { samples: usize },
+}
+
+/// The best configuration found, as an algorithm spec the CLI takes.
+pub struct Tuned {
+    pub algorithm: String,
+    pub training_score: f64,
+    pub validation_score: f64,
+    pub evaluated: usize,
+}
+
+impl Tuned {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "algorithm": self.algorithm,
+            "training_score": self.training_score,
+            "validation_score": self.validation_score,
+            "evaluated": self.evaluated,
+        })
+    }
+}
+
+type Candidate = Vec<f64>;
+
+fn spec(algorithm: &str, parameters: &[Parameter], candidate: &Candidate) -> String {
+    parameters.iter().zip(candidate).fold(algorithm.to_string(), |spec, (parameter, value)| {
+        if parameter.integer {
+            format!("{}:{}={}", spec, parameter.name, value.round() as i64)
+        } else {
+            // Three decimals keep the spec readable, finer differences are noise.
+            format!("{}:{}={}", spec, parameter.name, (value * 1000.0).round() / 1000.0)
+        }
+    })
+}
+
+/// Mean score over the instances run as `config` sets them up, a failed run scores 0.
+fn mean_score(spec: &str, instances: &[Instance], config: &RunConfig) -> f64 {
+    let total: i64 = instances.iter().map(|instance| run(instance, spec, config).map_or(0, |(score, _, _)| score)).sum();
+    total as f64 / instances.len().max(1) as f64
+}
+
+fn grid(parameters: &[Parameter], steps: usize) -> Vec<Candidate> {
+    parameters.iter().fold(vec![vec![]], |candidates, parameter| {
+        let mut values = parameter.range.grid(steps);
+        if parameter.integer {
+            values.iter_mut().for_each(|v| *v = v.round());
+            values.dedup();
+        }
+        candidates.iter().flat_map(|c| values.iter().map(move |v| {
+            let mut next = c.clone();
+            next.push(*v);
+            next
+        })).collect()
+    })
+}
+
+fn random(parameters: &[Parameter], samples: usize, rng: &mut Rng) -> Vec<Candidate> {
+    (0..samples).map(|_| parameters.iter().map(|p| p.range.sample(rng)).collect()).collect()
+}
+
+fn best_of(candidates: Vec<Candidate>, mut score: impl FnMut(&Candidate) -> f64) -> Result<(Candidate, f64), String> {
+    candidates.into_iter().map(|c| {
+        let score = score(&c);
+        (c, score)
+    }).max_by(|a, b| a.1.total_cmp(&b.1)).ok_or("There are no candidates to try.".to_string())
+}
+
+/// Searches the parameters of the configured algorithm for the best mean score on `training`,
+/// then scores the winner on `validation` (the training instances again if empty). Every run
+/// uses the start, budget, rules and objective of `config`, which the tuned config is saved with.
+pub fn tune(config: &RunConfig, parameters: &[Parameter], search: &Search, training: &[Instance],
+            validation: &[Instance]) -> Result<Tuned, String> {
+    let algorithm = config.algorithm.as_str();
+    if training.is_empty() {
+        return Err("There are no training instances.".to_string());
+    }
+    let mut rng = Rng::new(config.seed);
+    let mut evaluated = 0;
+    let mut evaluate = |candidate: &Candidate, instances: &[Instance]| {
+        let spec = spec(algorithm, parameters, candidate);
+        let score = mean_score(&spec, instances, config);
+        evaluated += 1;
+        tracing::info!("{} on {} instances: {:.1}", spec, instances.len(), score);
+        score
+    };
+
+    let (best, training_score) = match search {
+        Search::Grid { steps } => best_of(grid(parameters, *steps), |c| evaluate(c, training))?,
+        Search::Random { samples } => best_of(random(parameters, *samples, &mut rng), |c| evaluate(c, training))?,
+        Search::Halving { samples } => {
+            let mut candidates: Vec<(Candidate, f64)> = random(parameters, *samples, &mut rng)
+                .into_iter().map(|c| (c, 0.0)).collect();
+            let mut used = 1;
+            loop {
+                let instances = &training[..used.min(training.len())];
+                for (candidate, score) in candidates.iter_mut() {
+                    *score = evaluate(candidate, instances);
+                }
+                candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+                if candidates.len() <= 1 && instances.len() == training.len() {
+                    break;
+                }
+                candidates.truncate(candidates.len().div_ceil(2));
+                used *= 2;
+            }
+            candidates.into_iter().next().ok_or("There are no candidates to try.")?
+        }
+    };
+
+    let algorithm = spec(algorithm, parameters, &best);
+    let validation = if validation.is_empty() { training } else { validation };
+    Ok(Tuned { validation_score: mean_score(&algorithm, validation, config), algorithm, training_score, evaluated })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::{generate, GeneratorConfig};
+
+    fn instances(seeds: &[u64]) -> Vec<Instance> {
+        instances_from(seeds, "Neo4j")
+    }
+
+    fn instances_from(seeds: &[u64], start: &str) -> Vec<Instance> {
+        seeds.iter().map(|seed| {
+            let (nodes, relationships) = generate(&GeneratorConfig { nodes: 12, seed: *seed, start: start.to_string(), ..GeneratorConfig::default() });
+            Instance { name: seed.to_string(), nodes, relationships }
+        }).collect()
+    }
+
+    #[test]
+    fn grid_finds_the_best_combination() {
+        let parameters = vec![
+            Parameter { name: "max_depth".to_string(), range: "5,15".parse().unwrap(), integer: true },
+            Parameter { name: "collect_limit".to_string(), range: "0.5..1".parse().unwrap(), integer: false },
+        ];
+        assert_eq!(grid(&parameters, 3).len(), 6);
+        assert_eq!(spec("depth", &parameters, &vec![15.2, 0.75]), "depth:max_depth=15:collect_limit=0.75");
+
+        let training = instances(&[1, 2]);
+        let config = RunConfig { algorithm: "depth".to_string(), seed: 1, ..RunConfig::default() };
+        let tuned = tune(&config, &parameters, &Search::Grid { steps: 3 }, &training, &instances(&[3])).unwrap();
+        let best = grid(&parameters, 3).iter().map(|c| mean_score(&spec("depth", &parameters, c), &training, &config))
+            .fold(f64::MIN, f64::max);
+        assert_eq!(tuned.training_score, best);
+        assert_eq!(tuned.evaluated, 6);
+        assert_eq!(tuned.validation_score, mean_score(&tuned.algorithm, &instances(&[3]), &config));
+    }
+
+    #[test]
+    fn halving_keeps_the_better_half() {
+        let parameters = vec![Parameter { name: "collect_limit".to_string(), range: "0..2".parse().unwrap(), integer: false }];
+        let config = RunConfig { algorithm: "simple".to_string(), seed: 7, ..RunConfig::default() };
+        let tuned = tune(&config, &parameters, &Search::Halving { samples: 4 }, &instances(&[1, 2, 3]), &[]).unwrap();
+        // 4 on one instance, 2 on two, 1 on three.
+        assert_eq!(tuned.evaluated, 7);
+        assert!(tuned.algorithm.starts_with("simple:collect_limit="));
+        assert_eq!(tuned.training_score, tuned.validation_score);
+    }
+
+    #[test]
+    fn tunes_with_the_configured_start_and_budget() {
+        let parameters = vec![Parameter { name: "max_depth".to_string(), range: "5,15".parse().unwrap(), integer: true }];
+        let training = instances_from(&[1, 2], "Arkad");
+        let config = RunConfig { algorithm: "depth".to_string(), start: "Arkad".to_string(), budget: 400, ..RunConfig::default() };
+        let tuned = tune(&config, &parameters, &Search::Grid { steps: 2 }, &training, &[]).unwrap();
+        assert!(tuned.training_score > 0.0);
+        let scores: Vec<i64> = training.iter().map(|i| run(i, &tuned.algorithm, &config).unwrap().0).collect();
+        assert_eq!(tuned.training_score, scores.iter().sum::<i64>() as f64 / 2.0);
+        let longer = RunConfig { algorithm: "depth".to_string(), start: "Arkad".to_string(), ..RunConfig::default() };
+        assert!(mean_score(&tuned.algorithm, &training, &longer) > tuned.training_score);
+        // Under the default start none of the training instances can be run.
+        assert_eq!(mean_score(&tuned.algorithm, &training, &RunConfig::default()), 0.0);
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{Action, Node, Subnets};
+
+/// How much swag is worth, given what has already been collected.
+pub trait ValueModel {
+    fn name(&self) -> &str;
+    /// Value gained by collecting `node` after the collections in `history`.
+    fn marginal_value(&self, node: &Node, history: &[Action], nodes: &HashMap<String, Node>) -> i64;
+    /// Most `node` can be worth after any history, for bounding searches.
+    fn max_value(&self, node: &Node) -> i64 {
+        node.value
+    }
+}
+
+fn collected(history: &[Action]) -> impl Iterator<Item = &String> {
+    history.iter().filter(|a| a.collected).map(|a| &a.company)
+}
+
+/// Every company is worth its swag, as the graphathon scores it.
+pub struct Additive {}
+
+impl ValueModel for Additive {
+    fn name(&self) -> &str {
+        "additive"
+    }
+
+    fn marginal_value(&self, node: &Node, _history: &[Action], _nodes: &HashMap<String, Node>) -> i64 {
+        node.value
+    }
+}
+
+/// Each further company of the same category is worth `factor` times less, the third T-shirt
+/// is worth `factor²` of its swag. Companies without a category and negative swag are unaffected.
+pub struct DiminishingReturns {
+    pub(crate) factor: f64,
+}
+
+impl ValueModel for DiminishingReturns {
+    fn name(&self) -> &str {
+        "diminishing"
+    }
+
+    fn marginal_value(&self, node: &Node, history: &[Action], nodes: &HashMap<String, Node>) -> i64 {
+        match &node.category {
+            Some(category) if node.value > 0 => {
+                let already = collected(history)
+                    .filter(|c| nodes.get(*c).and_then(|n| n.category.as_ref()) == Some(category))
+                    .count();
+                (node.value as f64 * self.factor.powi(already as i32)).round() as i64
+            }
+            _ => node.value,
+        }
+    }
+}
+
+/// Adds `bonus` for the collection which completes a group, on top of the inner model.
+pub struct SetBonus {
+    pub(crate) inner: Box<dyn ValueModel>,
+    pub(crate) groups: Vec<Vec<String>>,
+    pub(crate) bonus: i64,
+}
+
+impl SetBonus {
+    /// Uses the subnets as the groups to complete.
+    pub fn from_subnets<K>(inner: Box<dyn ValueModel>, subnets: &Subnets<K>, bonus: i64) -> SetBonus {
+        let groups = subnets.subnets.iter()
+            .map(|s| s.nodes.iter().map(|n| n.name.clone()).collect())
+            .collect();
+        SetBonus{inner, groups, bonus}
+    }
+}
+
+impl ValueModel for SetBonus {
+    fn name(&self) -> &str {
+        "set bonus"
+    }
+
+    fn marginal_value(&self, node: &Node, history: &[Action], nodes: &HashMap<String, Node>) -> i64 {
+        let done: HashSet<&String> = collected(history).collect();
+        let mut value = self.inner.marginal_value(node, history, nodes);
+        if done.contains(&node.name) {
+            return value;
+        }
+        for group in &self.groups {
+            if group.contains(&node.name) && group.iter().all(|c| c == &node.name || done.contains(c)) {
+                value += self.bonus;
+            }
+        }
+        value
+    }
+
+    fn max_value(&self, node: &Node) -> i64 {
+        let groups = self.groups.iter().filter(|g| g.contains(&node.name)).count() as i64;
+        self.inner.max_value(node) + self.bonus * groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Path, State};
+
+    fn shirt(name: &str, value: i64, cost: i64) -> Node {
+        Node { category: Some("t-shirt".to_string()), ..Node::new(name.to_string(), value, cost) }
+    }
+
+    fn nodes() -> HashMap<String, Node> {
+        [shirt("A", 100, 10), shirt("B", 100, 10), shirt("C", 100, 10), shirt("Huge", 100, 500),
+         Node::new("Mug".to_string(), 40, 10)]
+            .into_iter().map(|n| (n.name.clone(), n)).collect()
+    }
+
+    fn history(collected: &[&str]) -> Vec<Action> {
+        collected.iter().map(|c| Action::new(c.to_string(), true)).collect()
+    }
+
+    #[test]
+    fn each_shirt_is_worth_less() {
+        let nodes = nodes();
+        let model = DiminishingReturns { factor: 0.5 };
+        for (k, expected) in [(0, 100), (1, 50), (2, 25)] {
+            let history = history(&["A", "B"][..k]);
+            assert_eq!(model.marginal_value(&nodes["C"], &history, &nodes), expected);
+        }
+        // Other categories and hops without collecting do not count.
+        let mut other = history(&["Mug"]);
+        other.push(Action::new("A".to_string(), false));
+        assert_eq!(model.marginal_value(&nodes["C"], &other, &nodes), 100);
+        assert_eq!(model.marginal_value(&nodes["Mug"], &history(&["A", "B"]), &nodes), 40);
+    }
+
+    #[test]
+    fn bonus_is_paid_once_when_the_set_completes() {
+        let nodes = nodes();
+        let model = SetBonus { inner: Box::new(Additive {}), groups: vec![vec!["A".to_string(), "B".to_string()]], bonus: 1000 };
+        assert_eq!(model.marginal_value(&nodes["A"], &[], &nodes), 100);
+        assert_eq!(model.marginal_value(&nodes["B"], &history(&["A"]), &nodes), 1100);
+        // Collecting again, or outside the set, pays no bonus.
+        assert_eq!(model.marginal_value(&nodes["B"], &history(&["A", "B"]), &nodes), 100);
+        assert_eq!(model.marginal_value(&nodes["Mug"], &history(&["A", "B"]), &nodes), 40);
+        assert_eq!(model.max_value(&nodes["A"]), 1100);
+    }
+
+    #[test]
+    fn only_collections_which_scored_count() {
+        let nodes = nodes();
+        let model = DiminishingReturns { factor: 0.5 };
+        let path = |to: &str| Path { to: to.to_string(), cost: 10 };
+        let mut state = State::starting("Mug".to_string(), 100, 4);
+        // Huge does not fit in the time, so it is recorded as not collected and B is worth it all.
+        state.goto(&path("Huge"), &nodes, &model, true);
+        assert!(!state.last_companies.last().unwrap().collected);
+        state.goto(&path("B"), &nodes, &model, true);
+        state.goto(&path("A"), &nodes, &model, true);
+        assert_eq!(state.score, 150);
+        let collected: Vec<&str> = collected(&state.last_companies).map(|c| c.as_str()).collect();
+        assert_eq!(collected, ["B", "A"]);
+    }
+}